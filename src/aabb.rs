@@ -0,0 +1,122 @@
+use std::ops::Range;
+
+use crate::types::{Point3, Ray, Vector3};
+
+/// Axis-aligned bounding box.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Point3,
+    pub max: Point3,
+}
+
+impl Default for Aabb {
+    fn default() -> Self {
+        Self::EMPTY
+    }
+}
+
+impl Aabb {
+    /// A box containing nothing. It is the identity element of [`Aabb::union`].
+    pub const EMPTY: Aabb = Aabb {
+        min: Point3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+        max: Point3::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+    };
+
+    /// Build the box spanned by two extreme points, given in any order.
+    pub fn new(a: Point3, b: Point3) -> Self {
+        Self {
+            min: a.inf(&b),
+            max: a.sup(&b),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: self.min.inf(&other.min),
+            max: self.max.sup(&other.max),
+        }
+    }
+
+    pub fn grow(&self, point: &Point3) -> Aabb {
+        Aabb {
+            min: self.min.inf(point),
+            max: self.max.sup(point),
+        }
+    }
+
+    /// Widen the box along every axis on which it is thinner than `delta`, so that flat objects
+    /// still get a box rays can hit.
+    pub fn padded(&self, delta: f64) -> Aabb {
+        let mut bbox = *self;
+        for axis in 0..3 {
+            if bbox.max[axis] - bbox.min[axis] < delta {
+                bbox.min[axis] -= delta / 2.0;
+                bbox.max[axis] += delta / 2.0;
+            }
+        }
+        bbox
+    }
+
+    pub fn centroid(&self) -> Point3 {
+        0.5 * (self.min + self.max)
+    }
+
+    /// Index of the axis along which the box is the largest.
+    pub fn longest_axis(&self) -> usize {
+        let extent = self.max - self.min;
+        if extent.x > extent.y && extent.x > extent.z {
+            0
+        } else if extent.y > extent.z {
+            1
+        } else {
+            2
+        }
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+
+        let extent = self.max - self.min;
+        2.0 * (extent.x * extent.y + extent.y * extent.z + extent.z * extent.x)
+    }
+
+    /// Slab test: return true if the ray enters the box within `t_range`.
+    pub fn hit(&self, ray: &Ray, t_range: Range<f64>) -> bool {
+        let inv_direction = ray.direction.map(|d| 1.0 / d);
+        self.hit_with_inv_direction(ray, &inv_direction, t_range)
+    }
+
+    /// Same as [`Aabb::hit`] with the component-wise inverse of the ray direction precomputed, so
+    /// that it can be shared between the many boxes tested along a single ray.
+    pub fn hit_with_inv_direction(
+        &self,
+        ray: &Ray,
+        inv_direction: &Vector3,
+        t_range: Range<f64>,
+    ) -> bool {
+        let (mut t_min, mut t_max) = (t_range.start, t_range.end);
+
+        for axis in 0..3 {
+            let inv_d = inv_direction[axis];
+            let mut t0 = (self.min[axis] - ray.origin[axis]) * inv_d;
+            let mut t1 = (self.max[axis] - ray.origin[axis]) * inv_d;
+            if inv_d < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+
+            t_min = t_min.max(t0);
+            t_max = t_max.min(t1);
+            if t_max <= t_min {
+                return false;
+            }
+        }
+
+        true
+    }
+}
@@ -0,0 +1,456 @@
+use std::fmt;
+use std::ops::Range;
+
+use crate::aabb::Aabb;
+use crate::object::{Hit, Hittable};
+use crate::types::{Point3, Ray};
+
+// Relative costs of visiting an interior node and of intersecting a single object, used by the
+// surface area heuristic.
+const TRAVERSAL_COST: f64 = 0.125;
+const INTERSECTION_COST: f64 = 1.0;
+
+// Upper bound on the hierarchy depth, which also bounds the traversal stack.
+const MAX_DEPTH: usize = 64;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SplitMethod {
+    /// Split at the middle of the centroid bounds along the longest axis.
+    Middle,
+
+    /// Binned surface area heuristic.
+    Sah { bins: usize },
+}
+
+impl Default for SplitMethod {
+    fn default() -> Self {
+        SplitMethod::Sah { bins: 16 }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct BvhStats {
+    pub objects: usize,
+    pub nodes: usize,
+    pub leaves: usize,
+    pub depth: usize,
+
+    // Expected cost of a random ray against the hierarchy, in units of object intersections
+    pub cost: f64,
+}
+
+impl BvhStats {
+    /// Expected cost of a random ray when every object is tested, as the `[T]` traversal does.
+    pub fn naive_cost(&self) -> f64 {
+        INTERSECTION_COST * self.objects as f64
+    }
+}
+
+impl fmt::Display for BvhStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} objects, {} nodes, {} leaves, depth {}, cost {:.2} (naive {:.2})",
+            self.objects,
+            self.nodes,
+            self.leaves,
+            self.depth,
+            self.cost,
+            self.naive_cost()
+        )
+    }
+}
+
+#[derive(Debug)]
+pub struct BvhBuilder {
+    split_method: SplitMethod,
+    max_leaf_size: usize,
+}
+
+impl Default for BvhBuilder {
+    fn default() -> Self {
+        Self {
+            split_method: SplitMethod::default(),
+            max_leaf_size: 4,
+        }
+    }
+}
+
+impl BvhBuilder {
+    pub fn with_split_method(mut self, split_method: SplitMethod) -> Self {
+        self.split_method = split_method;
+        self
+    }
+
+    pub fn with_max_leaf_size(mut self, max_leaf_size: usize) -> Self {
+        self.max_leaf_size = max_leaf_size.max(1);
+        self
+    }
+
+    pub fn build<T: Hittable>(&self, objects: Vec<T>) -> Bvh<T> {
+        let mut primitives: Vec<_> = objects
+            .iter()
+            .enumerate()
+            .map(|(index, object)| {
+                let bbox = object.bounding_box();
+                BuildPrimitive {
+                    index,
+                    bbox,
+                    centroid: bbox.centroid(),
+                }
+            })
+            .collect();
+
+        let mut nodes = Vec::with_capacity(2 * objects.len());
+        let mut stats = BvhStats {
+            objects: objects.len(),
+            ..BvhStats::default()
+        };
+
+        if !primitives.is_empty() {
+            self.build_node(&mut primitives, 0, 1, &mut nodes, &mut stats);
+
+            // Costs were accumulated as surface areas, normalize them by the root's one.
+            let root_area = nodes[0].bbox.surface_area();
+            stats.cost = if root_area > 0.0 {
+                stats.cost / root_area
+            } else {
+                INTERSECTION_COST * primitives.len() as f64
+            };
+        }
+
+        // Reorder the objects so that each leaf refers to a contiguous range.
+        let mut objects: Vec<_> = objects.into_iter().map(Some).collect();
+        let objects = primitives
+            .iter()
+            .map(|primitive| objects[primitive.index].take().unwrap())
+            .collect();
+
+        Bvh {
+            objects,
+            nodes,
+            stats,
+        }
+    }
+
+    /// Recursively build the subtree for `primitives`, whose position within the whole slice
+    /// starts at `offset`, and append its nodes in depth-first order.
+    fn build_node(
+        &self,
+        primitives: &mut [BuildPrimitive],
+        offset: usize,
+        depth: usize,
+        nodes: &mut Vec<LinearNode>,
+        stats: &mut BvhStats,
+    ) {
+        let bbox = primitives
+            .iter()
+            .fold(Aabb::EMPTY, |bbox, primitive| bbox.union(&primitive.bbox));
+        let count = primitives.len();
+
+        stats.nodes += 1;
+        stats.depth = stats.depth.max(depth);
+
+        let split = if count == 1 || depth >= MAX_DEPTH {
+            None
+        } else {
+            self.split(primitives, &bbox)
+        };
+
+        let Some((mid, axis)) = split else {
+            stats.leaves += 1;
+            stats.cost += INTERSECTION_COST * count as f64 * bbox.surface_area();
+            nodes.push(LinearNode {
+                bbox,
+                kind: NodeKind::Leaf { offset, count },
+            });
+            return;
+        };
+
+        stats.cost += TRAVERSAL_COST * bbox.surface_area();
+
+        // The first child immediately follows its parent, the second one is patched in once the
+        // first subtree is complete.
+        let index = nodes.len();
+        nodes.push(LinearNode {
+            bbox,
+            kind: NodeKind::Interior {
+                second_child: 0,
+                axis,
+            },
+        });
+
+        let (left, right) = primitives.split_at_mut(mid);
+        self.build_node(left, offset, depth + 1, nodes, stats);
+        let second_child = nodes.len();
+        self.build_node(right, offset + mid, depth + 1, nodes, stats);
+
+        nodes[index].kind = NodeKind::Interior { second_child, axis };
+    }
+
+    /// Partition `primitives` in place and return the split position and axis, or `None` if they
+    /// should rather stay together in a leaf.
+    fn split(&self, primitives: &mut [BuildPrimitive], bbox: &Aabb) -> Option<(usize, usize)> {
+        let centroid_bounds = primitives.iter().fold(Aabb::EMPTY, |bounds, primitive| {
+            bounds.grow(&primitive.centroid)
+        });
+        let axis = centroid_bounds.longest_axis();
+        let (min, max) = (centroid_bounds.min[axis], centroid_bounds.max[axis]);
+
+        if max <= min {
+            // All centroids coincide, no partition can separate them.
+            return if primitives.len() <= self.max_leaf_size {
+                None
+            } else {
+                Some((primitives.len() / 2, axis))
+            };
+        }
+
+        match self.split_method {
+            SplitMethod::Middle => {
+                if primitives.len() <= self.max_leaf_size {
+                    return None;
+                }
+
+                let middle = 0.5 * (min + max);
+                let mid = partition(primitives, |primitive| primitive.centroid[axis] < middle);
+                if mid == 0 || mid == primitives.len() {
+                    Some((median_split(primitives, axis), axis))
+                } else {
+                    Some((mid, axis))
+                }
+            },
+            SplitMethod::Sah { bins } => {
+                let bins = bins.max(2);
+                let bin_of = |centroid: &Point3| {
+                    (((centroid[axis] - min) / (max - min) * bins as f64) as usize).min(bins - 1)
+                };
+
+                let mut bin_bounds = vec![Aabb::EMPTY; bins];
+                let mut bin_counts = vec![0; bins];
+                for primitive in primitives.iter() {
+                    let bin = bin_of(&primitive.centroid);
+                    bin_bounds[bin] = bin_bounds[bin].union(&primitive.bbox);
+                    bin_counts[bin] += 1;
+                }
+
+                // Sweep from the right to get the area and count of everything above each
+                // boundary, then from the left to evaluate the cost of splitting there.
+                let mut above = vec![(0.0, 0); bins];
+                let (mut bounds, mut count) = (Aabb::EMPTY, 0);
+                for bin in (1..bins).rev() {
+                    bounds = bounds.union(&bin_bounds[bin]);
+                    count += bin_counts[bin];
+                    above[bin] = (bounds.surface_area(), count);
+                }
+
+                let mut best = (f64::INFINITY, 0);
+                let (mut bounds, mut count) = (Aabb::EMPTY, 0);
+                for bin in 1..bins {
+                    bounds = bounds.union(&bin_bounds[bin - 1]);
+                    count += bin_counts[bin - 1];
+                    let (area_above, count_above) = above[bin];
+                    if count == 0 || count_above == 0 {
+                        continue;
+                    }
+
+                    let cost =
+                        bounds.surface_area() * count as f64 + area_above * count_above as f64;
+                    if cost < best.0 {
+                        best = (cost, bin);
+                    }
+                }
+
+                let (best_cost, best_bin) = best;
+                let split_cost =
+                    TRAVERSAL_COST + INTERSECTION_COST * best_cost / bbox.surface_area();
+                let leaf_cost = INTERSECTION_COST * primitives.len() as f64;
+
+                if primitives.len() <= self.max_leaf_size && split_cost >= leaf_cost {
+                    None
+                } else if best_cost.is_finite() {
+                    let mid = partition(primitives, |primitive| {
+                        bin_of(&primitive.centroid) < best_bin
+                    });
+                    Some((mid, axis))
+                } else {
+                    Some((median_split(primitives, axis), axis))
+                }
+            },
+        }
+    }
+}
+
+struct BuildPrimitive {
+    index: usize,
+    bbox: Aabb,
+    centroid: Point3,
+}
+
+/// Move the primitives matching `predicate` to the front and return how many there are.
+fn partition(
+    primitives: &mut [BuildPrimitive],
+    predicate: impl Fn(&BuildPrimitive) -> bool,
+) -> usize {
+    let mut mid = 0;
+    for i in 0..primitives.len() {
+        if predicate(&primitives[i]) {
+            primitives.swap(i, mid);
+            mid += 1;
+        }
+    }
+    mid
+}
+
+/// Sort the primitives along `axis` and return the index splitting them in half.
+fn median_split(primitives: &mut [BuildPrimitive], axis: usize) -> usize {
+    let mid = primitives.len() / 2;
+    primitives.select_nth_unstable_by(mid, |a, b| a.centroid[axis].total_cmp(&b.centroid[axis]));
+    mid
+}
+
+#[derive(Copy, Clone, Debug)]
+enum NodeKind {
+    Leaf { offset: usize, count: usize },
+    Interior { second_child: usize, axis: usize },
+}
+
+#[derive(Copy, Clone, Debug)]
+struct LinearNode {
+    bbox: Aabb,
+    kind: NodeKind,
+}
+
+/// Bounding volume hierarchy over a set of objects.
+///
+/// Nodes are stored in a single array in depth-first order, so that the first child of an
+/// interior node directly follows it, and are traversed with an explicit stack.
+///
+/// Hierarchies can be nested: a top-level hierarchy over [`Instance`](crate::instance::Instance)s
+/// sharing bottom-level hierarchies of primitives only stores each distinct geometry once, and
+/// can be refitted or rebuilt cheaply when the instances move.
+pub struct Bvh<T = Box<dyn Hittable>> {
+    objects: Vec<T>,
+    nodes: Vec<LinearNode>,
+    stats: BvhStats,
+}
+
+impl<T: Hittable> Bvh<T> {
+    /// Build a hierarchy with the default [`BvhBuilder`] settings.
+    pub fn new(objects: Vec<T>) -> Self {
+        BvhBuilder::default().build(objects)
+    }
+
+    pub fn stats(&self) -> &BvhStats {
+        &self.stats
+    }
+
+    /// Objects in the order of the leaves, which is not the order they were given in.
+    pub fn objects(&self) -> &[T] {
+        &self.objects
+    }
+
+    /// Objects in the order of the leaves, to be updated in place before a [`Bvh::refit`].
+    pub fn objects_mut(&mut self) -> &mut [T] {
+        &mut self.objects
+    }
+
+    pub fn into_objects(self) -> Vec<T> {
+        self.objects
+    }
+
+    /// Update the node boxes after the objects have moved, keeping the tree structure. This is
+    /// much cheaper than building the hierarchy again, but the tree gets less efficient as
+    /// objects move away from where they were at build time.
+    pub fn refit(&mut self) {
+        let mut cost = 0.0;
+
+        // Children always come after their parent, so going backwards visits them first.
+        for index in (0..self.nodes.len()).rev() {
+            let node = self.nodes[index];
+            let bbox = match node.kind {
+                NodeKind::Leaf { offset, count } => {
+                    let bbox = self.objects[offset..offset + count].bounding_box();
+                    cost += INTERSECTION_COST * count as f64 * bbox.surface_area();
+                    bbox
+                },
+                NodeKind::Interior { second_child, .. } => {
+                    let bbox = self.nodes[index + 1]
+                        .bbox
+                        .union(&self.nodes[second_child].bbox);
+                    cost += TRAVERSAL_COST * bbox.surface_area();
+                    bbox
+                },
+            };
+            self.nodes[index].bbox = bbox;
+        }
+
+        if let Some(root) = self.nodes.first() {
+            let root_area = root.bbox.surface_area();
+            self.stats.cost = if root_area > 0.0 {
+                cost / root_area
+            } else {
+                INTERSECTION_COST * self.objects.len() as f64
+            };
+        }
+    }
+}
+
+impl<T: Hittable> Hittable for Bvh<T> {
+    fn hit(&self, ray: &Ray, t_range: Range<f64>) -> Option<Hit<'_>> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let inv_direction = ray.direction.map(|d| 1.0 / d);
+        let mut nearest_hit = None;
+        let mut closest_so_far = t_range.end;
+
+        let mut stack = [0; MAX_DEPTH];
+        let mut stack_len = 0;
+        let mut index = 0;
+
+        loop {
+            let node = &self.nodes[index];
+            if node
+                .bbox
+                .hit_with_inv_direction(ray, &inv_direction, t_range.start..closest_so_far)
+            {
+                match node.kind {
+                    NodeKind::Leaf { offset, count } => {
+                        for object in &self.objects[offset..offset + count] {
+                            if let Some(hit) = object.hit(ray, t_range.start..closest_so_far) {
+                                closest_so_far = hit.t;
+                                nearest_hit = Some(hit);
+                            }
+                        }
+                    },
+                    NodeKind::Interior { second_child, axis } => {
+                        // Visit the child nearest to the ray origin first.
+                        if inv_direction[axis] < 0.0 {
+                            stack[stack_len] = index + 1;
+                            index = second_child;
+                        } else {
+                            stack[stack_len] = second_child;
+                            index += 1;
+                        }
+                        stack_len += 1;
+                        continue;
+                    },
+                }
+            }
+
+            if stack_len == 0 {
+                break;
+            }
+            stack_len -= 1;
+            index = stack[stack_len];
+        }
+
+        nearest_hit
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.nodes.first().map_or(Aabb::EMPTY, |root| root.bbox)
+    }
+}
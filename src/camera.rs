@@ -1,4 +1,4 @@
-use crate::types::{Basis, Point3, Vector3};
+use crate::types::{concentric_disk_sample, Basis, Point2, Point3, Vector3};
 
 #[derive(Debug, Default)]
 pub struct Viewport {
@@ -50,6 +50,9 @@ pub struct Camera {
 
     // Distance from camera lookfrom point to plane of perfect focus
     focus_dist: f64,
+
+    // Times the shutter opens and closes at
+    shutter: (f64, f64),
 }
 
 impl Default for Camera {
@@ -65,6 +68,7 @@ impl Default for Camera {
             basis: Basis::default(),
             defocus: Defocus::default(),
             focus_dist: 10.0,
+            shutter: (0.0, 0.0),
         }
     }
 }
@@ -104,6 +108,13 @@ impl Camera {
         self
     }
 
+    /// Let the shutter open from `open` to `close`, the scene being seen at every time in
+    /// between. By default it only opens at time 0.
+    pub fn with_shutter(mut self, open: f64, close: f64) -> Self {
+        self.shutter = (open, close.max(open));
+        self
+    }
+
     pub fn with_viewport_size(mut self, image_width: usize, image_height: usize) -> Self {
         // Calculate the actual aspect ratio.
         let aspect_ration = image_width as f64 / image_height as f64;
@@ -148,17 +159,28 @@ impl Camera {
         self.lookfrom
     }
 
-    pub fn origin(&self) -> Point3 {
+    /// Ray origin for the lens sample `lens` in 0..1 × 0..1.
+    pub fn origin(&self, lens: Point2) -> Point3 {
         if self.defocus.angle > 0.0 {
-            self.defocus_disk_sample()
+            self.defocus_disk_sample(lens)
         } else {
             self.lookfrom()
         }
     }
 
-    fn defocus_disk_sample(&self) -> Vector3 {
-        // Returns a random point in the camera defocus disk.
-        let p = random_in_unit_disk();
+    pub fn shutter(&self) -> (f64, f64) {
+        self.shutter
+    }
+
+    /// Ray time for the sample `u` in 0..1, within the shutter interval.
+    pub fn time(&self, u: f64) -> f64 {
+        let (open, close) = self.shutter;
+        open + u * (close - open)
+    }
+
+    fn defocus_disk_sample(&self, lens: Point2) -> Vector3 {
+        // Returns the point of the camera defocus disk matching the sample.
+        let p = concentric_disk_sample(lens);
         self.lookfrom + (p[0] * self.defocus.disk_u) + (p[1] * self.defocus.disk_v)
     }
 
@@ -170,16 +192,3 @@ impl Camera {
         viewport_upper_left + (self.pixel.delta_u + self.pixel.delta_v) / 2.0
     }
 }
-
-fn random_in_unit_disk() -> Vector3 {
-    loop {
-        let p = Vector3::new(
-            rand::random_range(-1.0..1.0),
-            rand::random_range(-1.0..1.0),
-            0.0,
-        );
-        if p.magnitude_squared() < 1.0 {
-            return p;
-        }
-    }
-}
@@ -0,0 +1,184 @@
+//! Snapshots of a progressive render, from which it can be resumed.
+//!
+//! A checkpoint file is made of little endian values: the `RTCKPT04` magic number, the image
+//! width and height, the render seed and the scene hash as `u64`, then for every pixel row by row
+//! the [`PixelStats`] mean weighted radiance as three `f64`, sum of squared deviations, mean
+//! filter weight, sum of squared weight deviations and sum of deviation products as `f64` and
+//! sample count as `u32`.
+
+use std::fs::{self, File};
+use std::hash::Hasher;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use derive_more::{Display, From};
+
+use crate::frame::{Accumulator, PixelStats};
+use crate::types::Color;
+
+const MAGIC: &[u8; 8] = b"RTCKPT04";
+
+#[derive(Debug, Display, From)]
+pub enum CheckpointError {
+    #[display("{_0}")]
+    Io(io::Error),
+
+    #[display("not a checkpoint file")]
+    InvalidFormat,
+
+    #[display("checkpoint was rendered from a different scene or settings")]
+    SceneMismatch,
+
+    #[display("checkpoint is {found_width}x{found_height}, the image is {width}x{height}")]
+    SizeMismatch {
+        width: usize,
+        height: usize,
+        found_width: usize,
+        found_height: usize,
+    },
+}
+
+impl std::error::Error for CheckpointError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CheckpointError::Io(source) => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Accumulated samples of a render, along with what is needed to continue it: the seed the
+/// samples are derived from, and a hash identifying the scene they were rendered from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Checkpoint {
+    pub seed: u64,
+    pub scene_hash: u64,
+    pub accumulator: Accumulator,
+}
+
+impl Checkpoint {
+    /// Write the checkpoint next to `path` first and then move it in place, so that an
+    /// interrupted save leaves the previous checkpoint intact.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), CheckpointError> {
+        let path = path.as_ref();
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+
+        let mut out = BufWriter::new(File::create(&temporary)?);
+        self.write(&mut out)?;
+        out.into_inner()
+            .map_err(|err| err.into_error())?
+            .sync_all()?;
+        fs::rename(&temporary, path)?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, CheckpointError> {
+        Self::read(&mut BufReader::new(File::open(path)?))
+    }
+
+    pub fn write(&self, out: &mut impl Write) -> Result<(), CheckpointError> {
+        let accumulator = &self.accumulator;
+        out.write_all(MAGIC)?;
+        for value in [
+            accumulator.width() as u64,
+            accumulator.height() as u64,
+            self.seed,
+            self.scene_hash,
+        ] {
+            out.write_all(&value.to_le_bytes())?;
+        }
+
+        for pixel in accumulator.pixels() {
+            for value in [
+                pixel.mean.x,
+                pixel.mean.y,
+                pixel.mean.z,
+                pixel.m2,
+                pixel.weight,
+                pixel.weight_m2,
+                pixel.cross_m2,
+            ] {
+                out.write_all(&value.to_le_bytes())?;
+            }
+            out.write_all(&pixel.samples.to_le_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    pub fn read(input: &mut impl Read) -> Result<Self, CheckpointError> {
+        let mut magic = [0; 8];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(CheckpointError::InvalidFormat);
+        }
+
+        let width = read_u64(input)? as usize;
+        let height = read_u64(input)? as usize;
+        let seed = read_u64(input)?;
+        let scene_hash = read_u64(input)?;
+        let len = width
+            .checked_mul(height)
+            .ok_or(CheckpointError::InvalidFormat)?;
+
+        // The buffer is not preallocated, a corrupted size runs into the end of the file rather
+        // than out of memory.
+        let mut pixels = Vec::new();
+        for _ in 0..len {
+            let r = f64::from_bits(read_u64(input)?);
+            let g = f64::from_bits(read_u64(input)?);
+            let b = f64::from_bits(read_u64(input)?);
+            let m2 = f64::from_bits(read_u64(input)?);
+            let weight = f64::from_bits(read_u64(input)?);
+            let weight_m2 = f64::from_bits(read_u64(input)?);
+            let cross_m2 = f64::from_bits(read_u64(input)?);
+
+            let mut samples = [0; 4];
+            input.read_exact(&mut samples)?;
+            pixels.push(PixelStats {
+                mean: Color::new(r, g, b),
+                m2,
+                weight,
+                weight_m2,
+                cross_m2,
+                samples: u32::from_le_bytes(samples),
+            });
+        }
+
+        Ok(Self {
+            seed,
+            scene_hash,
+            accumulator: Accumulator::from_pixels(width, height, pixels),
+        })
+    }
+}
+
+fn read_u64(input: &mut impl Read) -> Result<u64, CheckpointError> {
+    let mut bytes = [0; 8];
+    input.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+/// 64-bit FNV-1a hasher, whose output unlike the one of the standard library hasher is stable
+/// across builds, for computing the scene hash of checkpoints.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SceneHasher(u64);
+
+impl Default for SceneHasher {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for SceneHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
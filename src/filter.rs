@@ -0,0 +1,293 @@
+//! Pixel reconstruction filters.
+//!
+//! Rather than splatting every sample into the neighbouring pixels, the samples of a pixel are
+//! spread over the support of the filter with a density proportional to its absolute value, and
+//! weighted so that their mean is the filtered image value at the pixel. This keeps every pixel
+//! independent of its neighbours.
+//!
+//! The density is tabulated in piecewise constant bins, so the weights are only all 1 for the box
+//! filter. They vary within each bin for other filters, slightly for the tent and Gaussian ones
+//! and with negative values for the ones with negative lobes, which is why pixels are normalized
+//! by their mean weight, see [`PixelStats::radiance`](crate::frame::PixelStats::radiance).
+
+use std::f64::consts::PI;
+use std::fmt;
+
+use crate::types::{Point2, Vector2};
+
+/// Separable filter, whose 2D value at an offset `(x, y)` from the pixel center is
+/// `evaluate(x) * evaluate(y)`.
+pub trait Filter: fmt::Debug + Send + Sync {
+    /// Half width of the support of the filter, in pixels.
+    fn radius(&self) -> f64;
+
+    /// Value of the filter at `x` pixels from the center, 0 beyond the radius.
+    fn evaluate(&self, x: f64) -> f64;
+}
+
+/// Constant weight over a square, a radius of 0.5 covering exactly one pixel.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BoxFilter {
+    radius: f64,
+}
+
+impl BoxFilter {
+    pub fn new(radius: f64) -> Self {
+        Self { radius }
+    }
+}
+
+impl Default for BoxFilter {
+    fn default() -> Self {
+        Self::new(0.5)
+    }
+}
+
+impl Filter for BoxFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f64) -> f64 {
+        if x.abs() <= self.radius {
+            1.0
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Weight decreasing linearly from the center to the radius.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TentFilter {
+    radius: f64,
+}
+
+impl TentFilter {
+    pub fn new(radius: f64) -> Self {
+        Self { radius }
+    }
+}
+
+impl Default for TentFilter {
+    fn default() -> Self {
+        Self::new(1.0)
+    }
+}
+
+impl Filter for TentFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f64) -> f64 {
+        (1.0 - x.abs() / self.radius).max(0.0)
+    }
+}
+
+/// Gaussian of standard deviation `sigma`, shifted down to reach 0 at the radius.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GaussianFilter {
+    radius: f64,
+    sigma: f64,
+}
+
+impl GaussianFilter {
+    pub fn new(radius: f64, sigma: f64) -> Self {
+        Self { radius, sigma }
+    }
+
+    fn gaussian(&self, x: f64) -> f64 {
+        (-x * x / (2.0 * self.sigma * self.sigma)).exp()
+    }
+}
+
+impl Default for GaussianFilter {
+    fn default() -> Self {
+        Self::new(1.5, 0.5)
+    }
+}
+
+impl Filter for GaussianFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f64) -> f64 {
+        (self.gaussian(x) - self.gaussian(self.radius)).max(0.0)
+    }
+}
+
+/// Mitchell–Netravali cubic filter with parameters `b` and `c`, scaled to the radius.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MitchellFilter {
+    radius: f64,
+    b: f64,
+    c: f64,
+}
+
+impl MitchellFilter {
+    pub fn new(radius: f64, b: f64, c: f64) -> Self {
+        Self { radius, b, c }
+    }
+}
+
+impl Default for MitchellFilter {
+    fn default() -> Self {
+        Self::new(2.0, 1.0 / 3.0, 1.0 / 3.0)
+    }
+}
+
+impl Filter for MitchellFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f64) -> f64 {
+        let (b, c) = (self.b, self.c);
+        let x = 2.0 * x.abs() / self.radius;
+
+        let value = if x > 2.0 {
+            0.0
+        } else if x > 1.0 {
+            (-b - 6.0 * c) * x.powi(3)
+                + (6.0 * b + 30.0 * c) * x.powi(2)
+                + (-12.0 * b - 48.0 * c) * x
+                + (8.0 * b + 24.0 * c)
+        } else {
+            (12.0 - 9.0 * b - 6.0 * c) * x.powi(3)
+                + (-18.0 + 12.0 * b + 6.0 * c) * x.powi(2)
+                + (6.0 - 2.0 * b)
+        };
+
+        value / 6.0
+    }
+}
+
+/// Sinc windowed by a sinc stretched to the radius.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LanczosFilter {
+    radius: f64,
+}
+
+impl LanczosFilter {
+    pub fn new(radius: f64) -> Self {
+        Self { radius }
+    }
+}
+
+impl Default for LanczosFilter {
+    fn default() -> Self {
+        Self::new(3.0)
+    }
+}
+
+impl Filter for LanczosFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f64) -> f64 {
+        if x.abs() > self.radius {
+            0.0
+        } else {
+            sinc(x) * sinc(x / self.radius)
+        }
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-5 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+// Number of bins of the tabulated filter distributions.
+const FILTER_BINS: usize = 256;
+
+/// Distribution of sample offsets following the absolute value of a filter, tabulated in
+/// piecewise constant bins.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FilterSampler {
+    filter_radius: f64,
+    bin_width: f64,
+
+    // Cumulative distribution over the bins, from 0 to 1
+    cdf: Vec<f64>,
+
+    // Filter values, so that offsets can be weighted without access to the filter
+    values: Vec<f64>,
+
+    // Integral of the filter over its support
+    integral: f64,
+}
+
+impl FilterSampler {
+    pub fn new(filter: &dyn Filter) -> Self {
+        let radius = filter.radius().max(1e-3);
+        let bin_width = 2.0 * radius / FILTER_BINS as f64;
+        let edge = |bin: usize| -radius + bin as f64 * bin_width;
+
+        // Bins are weighted by the largest value among their edges and center, so that no part
+        // of the support where the filter is not 0 is left out.
+        let mut cdf = Vec::with_capacity(FILTER_BINS + 1);
+        cdf.push(0.0);
+        for bin in 0..FILTER_BINS {
+            let weight = [edge(bin), edge(bin) + 0.5 * bin_width, edge(bin + 1)]
+                .map(|x| filter.evaluate(x).abs())
+                .into_iter()
+                .fold(0.0, f64::max);
+            cdf.push(cdf[bin] + weight);
+        }
+        let total = cdf[FILTER_BINS];
+        cdf.iter_mut().for_each(|value| *value /= total);
+
+        // Finely tabulated values, also giving the integral with the midpoint rule.
+        let resolution = 16 * FILTER_BINS;
+        let step = 2.0 * radius / resolution as f64;
+        let values: Vec<f64> = (0..resolution)
+            .map(|k| filter.evaluate(-radius + (k as f64 + 0.5) * step))
+            .collect();
+        let integral = values.iter().sum::<f64>() * step;
+
+        Self {
+            filter_radius: radius,
+            bin_width,
+            cdf,
+            values,
+            integral,
+        }
+    }
+
+    /// Offset from the pixel center for the 2D sample `u`, and its weight, whose expected value
+    /// is 1.
+    pub fn sample(&self, u: Point2) -> (Vector2, f64) {
+        let (x, weight_x) = self.sample_1d(u.x);
+        let (y, weight_y) = self.sample_1d(u.y);
+        (Vector2::new(x, y), weight_x * weight_y)
+    }
+
+    fn sample_1d(&self, u: f64) -> (f64, f64) {
+        let bin = self
+            .cdf
+            .partition_point(|&value| value <= u)
+            .clamp(1, FILTER_BINS)
+            - 1;
+        let (low, high) = (self.cdf[bin], self.cdf[bin + 1]);
+        let fraction = if high > low {
+            (u - low) / (high - low)
+        } else {
+            0.5
+        };
+        let x = -self.filter_radius + (bin as f64 + fraction) * self.bin_width;
+
+        let pdf = (high - low) / self.bin_width;
+        let index = ((x + self.filter_radius) / (2.0 * self.filter_radius)
+            * self.values.len() as f64) as usize;
+        let value = self.values[index.min(self.values.len() - 1)];
+
+        (x, value / (pdf * self.integral))
+    }
+}
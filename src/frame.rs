@@ -0,0 +1,214 @@
+use crate::types::Color;
+
+/// Image buffer of `width` by `height` linear radiance values, stored row by row from the top left
+/// corner.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+}
+
+impl Frame {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Color::BLACK; width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, i: usize, j: usize) -> Color {
+        self.pixels[i + j * self.width]
+    }
+
+    /// Colors of the `len` pixels of row `j` starting at column `i`.
+    pub fn span_mut(&mut self, i: usize, j: usize, len: usize) -> &mut [Color] {
+        let start = i + j * self.width;
+        &mut self.pixels[start..start + len]
+    }
+
+    pub fn pixels(&self) -> &[Color] {
+        &self.pixels
+    }
+}
+
+/// Running statistics of the filter weighted radiance samples of a pixel, updated with Welford's
+/// algorithm.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct PixelStats {
+    pub mean: Color,
+
+    // Sum of the squared deviations of the weighted sample luminances from their mean
+    pub m2: f64,
+
+    // Mean filter weight of the samples
+    pub weight: f64,
+
+    // Sum of the squared deviations of the weights from their mean
+    pub weight_m2: f64,
+
+    // Sum of the products of the deviations of the weighted luminances and of the weights
+    pub cross_m2: f64,
+
+    pub samples: u32,
+}
+
+impl PixelStats {
+    /// Add a radiance `sample` with its filter `weight`.
+    pub fn add(&mut self, sample: Color, weight: f64) {
+        let sample = weight * sample;
+        let luminance = sample.luminance();
+        let previous_mean = self.mean.luminance();
+        let previous_weight = self.weight;
+
+        self.samples += 1;
+        let scale = 1.0 / self.samples as f64;
+        self.mean += scale * (sample - self.mean);
+        self.weight += scale * (weight - self.weight);
+        self.m2 += (luminance - previous_mean) * (luminance - self.mean.luminance());
+        self.weight_m2 += (weight - previous_weight) * (weight - self.weight);
+        self.cross_m2 += (luminance - previous_mean) * (weight - self.weight);
+    }
+
+    /// Estimated radiance of the pixel: the mean of the weighted samples normalized by the mean
+    /// weight, which cancels out most of the noise that negative filter lobes bring in flat
+    /// areas.
+    pub fn radiance(&self) -> Color {
+        if self.weight > 0.0 {
+            (1.0 / self.weight) * self.mean
+        } else {
+            self.mean
+        }
+    }
+
+    /// Unbiased sample variance of the luminance, as the samples contribute to the normalized
+    /// [`radiance`](Self::radiance).
+    ///
+    /// With `y` the weighted luminances, `w` the weights and `r` their ratio of means, this is the
+    /// variance of `y - r w` over the squared mean weight (first order delta method). It is the
+    /// plain variance of the luminances when every weight is 1.
+    pub fn variance(&self) -> f64 {
+        if self.samples < 2 {
+            return 0.0;
+        }
+
+        let m2 = if self.weight > 0.0 {
+            let ratio = self.mean.luminance() / self.weight;
+            let residual_m2 =
+                self.m2 - 2.0 * ratio * self.cross_m2 + ratio.powi(2) * self.weight_m2;
+            residual_m2.max(0.0) / self.weight.powi(2)
+        } else {
+            self.m2
+        };
+        m2 / (self.samples - 1) as f64
+    }
+
+    /// Standard error of the estimated luminance relative to the estimate itself, infinite
+    /// without at least two samples to estimate it from.
+    pub fn relative_error(&self) -> f64 {
+        if self.samples < 2 {
+            return f64::INFINITY;
+        }
+
+        // Keep dark pixels from needing an absurdly precise estimate.
+        const MIN_LUMINANCE: f64 = 1e-3;
+        let standard_error = (self.variance() / self.samples as f64).sqrt();
+        standard_error / self.radiance().luminance().max(MIN_LUMINANCE)
+    }
+}
+
+/// Statistics of the radiance samples of every pixel, from which the [`Frame`] of their means is
+/// resolved. Pixels may have received different numbers of samples.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Accumulator {
+    width: usize,
+    height: usize,
+    pixels: Vec<PixelStats>,
+}
+
+impl Accumulator {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![PixelStats::default(); width * height],
+        }
+    }
+
+    /// NOTE: `pixels` is assumed to hold `width * height` values.
+    pub(crate) fn from_pixels(width: usize, height: usize, pixels: Vec<PixelStats>) -> Self {
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, i: usize, j: usize) -> &PixelStats {
+        &self.pixels[i + j * self.width]
+    }
+
+    pub fn get_mut(&mut self, i: usize, j: usize) -> &mut PixelStats {
+        &mut self.pixels[i + j * self.width]
+    }
+
+    pub fn pixels(&self) -> &[PixelStats] {
+        &self.pixels
+    }
+
+    /// Statistics of every pixel, row by row from the top left corner.
+    pub fn pixels_mut(&mut self) -> &mut [PixelStats] {
+        &mut self.pixels
+    }
+
+    /// Fewest samples received by any pixel.
+    pub fn min_samples(&self) -> u32 {
+        self.pixels
+            .iter()
+            .map(|pixel| pixel.samples)
+            .min()
+            .unwrap_or_default()
+    }
+
+    /// Average number of samples per pixel.
+    pub fn mean_samples(&self) -> f64 {
+        let total: u64 = self.pixels.iter().map(|pixel| pixel.samples as u64).sum();
+        total as f64 / self.pixels.len().max(1) as f64
+    }
+
+    /// Radiance of every pixel, black for the ones without samples yet.
+    pub fn frame(&self) -> Frame {
+        self.resolve(PixelStats::radiance)
+    }
+
+    /// Gray levels showing the number of samples of every pixel, `scale` being the level of a
+    /// single sample.
+    pub fn samples_frame(&self, scale: f64) -> Frame {
+        self.resolve(|pixel| (scale * pixel.samples as f64) * Color::WHITE)
+    }
+
+    fn resolve(&self, color: impl Fn(&PixelStats) -> Color) -> Frame {
+        Frame {
+            width: self.width,
+            height: self.height,
+            pixels: self.pixels.iter().map(color).collect(),
+        }
+    }
+}
@@ -0,0 +1,128 @@
+//! Objects placed in the scene through a transform, so that a single one can be shared by several
+//! instances at different places.
+
+use std::ops::Range;
+use std::sync::Arc;
+
+use nalgebra::{Affine3, Isometry3, Matrix3, Matrix4, Unit};
+
+use crate::aabb::Aabb;
+use crate::object::{Hit, Hittable};
+use crate::types::{Point3, Ray, Vector3};
+
+/// Object transformed from its own space, where it is defined, to world space.
+#[derive(Clone)]
+pub struct Instance {
+    object: Arc<dyn Hittable>,
+    transform: Affine3<f64>,
+    inverse: Affine3<f64>,
+
+    // Inverse transpose of the linear part of the transform, which maps normals to world space
+    normal_matrix: Matrix3<f64>,
+
+    bbox: Aabb,
+}
+
+impl Instance {
+    /// NOTE: the parameter `transform` is assumed to be invertible.
+    pub fn new(object: Arc<dyn Hittable>, transform: Affine3<f64>) -> Self {
+        let inverse = transform.inverse();
+        let normal_matrix = inverse.matrix().fixed_view::<3, 3>(0, 0).transpose();
+        let bbox = transform_box(&transform, &object.bounding_box());
+
+        Self {
+            object,
+            transform,
+            inverse,
+            normal_matrix,
+            bbox,
+        }
+    }
+
+    pub fn from_isometry(object: Arc<dyn Hittable>, isometry: Isometry3<f64>) -> Self {
+        Self::new(object, nalgebra::convert(isometry))
+    }
+
+    /// Move the instance by `offset`, after its current transform.
+    pub fn translated(self, offset: Vector3) -> Self {
+        self.then(Matrix4::new_translation(&offset))
+    }
+
+    /// Rotate the instance around `axis` through the origin by `angle` in degrees, after its
+    /// current transform.
+    pub fn rotated(self, axis: Vector3, angle: f64) -> Self {
+        let rotation = Matrix4::from_axis_angle(&Unit::new_normalize(axis), angle.to_radians());
+        self.then(rotation)
+    }
+
+    /// Scale the instance by the factors along each axis, after its current transform.
+    ///
+    /// NOTE: the factors are assumed to be nonzero.
+    pub fn scaled(self, factors: Vector3) -> Self {
+        self.then(Matrix4::new_nonuniform_scaling(&factors))
+    }
+
+    pub fn object(&self) -> &Arc<dyn Hittable> {
+        &self.object
+    }
+
+    pub fn transform(&self) -> &Affine3<f64> {
+        &self.transform
+    }
+
+    /// Move the instance to a new place, such as for the next frame of an animation.
+    ///
+    /// NOTE: the parameter `transform` is assumed to be invertible.
+    pub fn set_transform(&mut self, transform: Affine3<f64>) {
+        *self = Self::new(Arc::clone(&self.object), transform);
+    }
+
+    fn then(self, matrix: Matrix4<f64>) -> Self {
+        let transform = Affine3::from_matrix_unchecked(matrix * self.transform.matrix());
+        Self::new(self.object, transform)
+    }
+}
+
+impl Hittable for Instance {
+    fn hit(&self, ray: &Ray, t_range: Range<f64>) -> Option<Hit<'_>> {
+        let origin = self.inverse * nalgebra::Point3::from(ray.origin);
+        let direction = self.inverse * ray.direction;
+
+        // The object space ray gets a unit direction too, so its parameter is the world space
+        // one scaled by the length of the transformed direction.
+        let scale = direction.norm();
+        let local_ray = Ray::new(origin.coords, direction).with_time(ray.time);
+        let local_range = t_range.start * scale..t_range.end * scale;
+        let hit = self.object.hit(&local_ray, local_range)?;
+
+        // Transforms keep the side of the surface the ray comes from, and so the orientation of
+        // the normal towards the ray and `front_face`.
+        Some(Hit {
+            t: hit.t / scale,
+            point: (self.transform * nalgebra::Point3::from(hit.point)).coords,
+            normal: (self.normal_matrix * hit.normal).normalize(),
+            ..hit
+        })
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
+
+/// Axis-aligned box enclosing the box `bbox` once transformed.
+fn transform_box(transform: &Affine3<f64>, bbox: &Aabb) -> Aabb {
+    if bbox.is_empty() {
+        return Aabb::EMPTY;
+    }
+
+    let extremes = [bbox.min, bbox.max];
+    (0..8).fold(Aabb::EMPTY, |transformed, corner| {
+        let point = Point3::new(
+            extremes[corner & 1].x,
+            extremes[(corner >> 1) & 1].y,
+            extremes[corner >> 2].z,
+        );
+        transformed.grow(&(transform * nalgebra::Point3::from(point)).coords)
+    })
+}
@@ -0,0 +1,24 @@
+pub mod aabb;
+pub mod bvh;
+pub mod camera;
+pub mod checkpoint;
+pub mod filter;
+pub mod frame;
+pub mod instance;
+pub mod light;
+pub mod material;
+pub mod motion;
+pub mod obj;
+pub mod object;
+pub mod output;
+pub mod planar;
+pub mod random;
+pub mod render;
+pub mod sampler;
+pub mod scene;
+pub mod sphere;
+pub mod texture;
+pub mod tonemap;
+pub mod triangle;
+pub mod types;
+pub mod utils;
@@ -0,0 +1,38 @@
+//! Light sources sampled explicitly, so that small emitters do not have to be found by chance.
+
+use crate::object::{Hit, Hittable};
+use crate::types::{Point2, Point3, Vector3};
+
+/// Point of a light source sampled from a shading point.
+#[derive(Copy, Clone, Debug)]
+pub struct LightSample<'a> {
+    /// Hit of the ray from the shading point towards the light, giving the emitted radiance.
+    pub hit: Hit<'a>,
+
+    /// Unit direction from the shading point towards the light.
+    pub direction: Vector3,
+
+    /// Probability density of the direction, with respect to solid angle.
+    pub pdf: f64,
+}
+
+/// Emissive object whose surface can be sampled as seen from a point.
+pub trait Light: Hittable {
+    /// Sample a point of the light visible from `origin` at `time`, using the 2D sample `u`.
+    /// Returns `None` when the light cannot be sampled from there.
+    fn sample(&self, origin: &Point3, time: f64, u: Point2) -> Option<LightSample<'_>>;
+
+    /// Probability density with respect to solid angle of `sample` returning the unit
+    /// `direction` from `origin` at `time`, 0 if the light is not in that direction.
+    fn pdf(&self, origin: &Point3, time: f64, direction: &Vector3) -> f64;
+}
+
+/// Pick one of `count` lights uniformly with the first dimension of `u`, which is rescaled to
+/// 0..1 so that both dimensions can still be used to sample the picked light.
+pub fn pick_light(count: usize, u: Point2) -> (usize, Point2) {
+    let scaled = u.x * count as f64;
+    let index = (scaled as usize).min(count - 1);
+    let x = (scaled - index as f64).clamp(0.0, 1.0 - f64::EPSILON);
+
+    (index, Point2::new(x, u.y))
+}
@@ -1,46 +1,661 @@
-use std::io;
+use std::error::Error;
+use std::hash::Hasher;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use std::{fs, io};
 
-use camera::Camera;
-use material::Dielectric;
+use clap::{Parser, ValueEnum};
+use nalgebra::Affine3;
+use rand::Rng as _;
+use ray_tracing_tutorial::bvh::Bvh;
+use ray_tracing_tutorial::camera::Camera;
+use ray_tracing_tutorial::checkpoint::{Checkpoint, SceneHasher};
+use ray_tracing_tutorial::filter::{
+    BoxFilter, GaussianFilter, LanczosFilter, MitchellFilter, TentFilter,
+};
+use ray_tracing_tutorial::instance::Instance;
+use ray_tracing_tutorial::material::{Dielectric, DiffuseLight, Lambertian, Metal};
+use ray_tracing_tutorial::motion::Motion;
+use ray_tracing_tutorial::object::Hittable;
+use ray_tracing_tutorial::output::{
+    self, BitDepth, ExrWriter, HdrWriter, ImageWriter, PfmWriter, PngWriter, PpmWriter, Precision,
+};
+use ray_tracing_tutorial::planar::{make_box, Quad};
+use ray_tracing_tutorial::random::{self, Rng};
+use ray_tracing_tutorial::render::{AdaptiveSampling, Background, Fading, MisHeuristic, Renderer};
+use ray_tracing_tutorial::sampler::{Halton, Independent, Sobol, Stratified};
+use ray_tracing_tutorial::scene::Scene;
+use ray_tracing_tutorial::sphere::Sphere;
+use ray_tracing_tutorial::tonemap::{
+    Aces, Clamp, DisplayTransform, Dither, Hable, Reinhard, Transfer,
+};
+use ray_tracing_tutorial::types::{Color, Point3, Vector3};
+use ray_tracing_tutorial::utils::{Logger, Timer};
 
-use crate::material::{Lambertian, Metal};
-use crate::object::Hittable;
-use crate::render::{Fading, Renderer};
-use crate::sphere::Sphere;
-use crate::types::{Color, Point3};
-use crate::utils::Logger;
+// Default 16:9 image size of the built-in scenes
+const IMAGE_WIDTH: usize = 1200;
+const IMAGE_HEIGHT: usize = 675;
 
-mod camera;
-mod material;
-mod object;
-mod render;
-mod sphere;
-mod types;
-mod utils;
+type BuiltinScene = fn(&mut Rng) -> Scene;
 
-const ASPECT_RATIO: f64 = 16.0 / 9.0;
+/// Built-in scenes, by name, built for a given scene generation seed. The command line options
+/// override their image size and render settings.
+const BUILTIN_SCENES: &[(&str, BuiltinScene)] = &[
+    ("final", final_scene),
+    ("simple", simple_scene),
+    ("cornell", cornell_scene),
+    ("bouncing", bouncing_scene),
+];
 
-fn main() {
+/// Render a scene and write the resulting image.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Cli {
+    /// Built-in scene name (final, simple, cornell, bouncing) or path to a scene description file
+    #[arg(short, long, value_name = "NAME|FILE", default_value = "final")]
+    scene: String,
+
+    /// Image width in pixels
+    #[arg(long)]
+    width: Option<usize>,
+
+    /// Image height in pixels, derived from the width and aspect ratio if not given
+    #[arg(long)]
+    height: Option<usize>,
+
+    /// Image aspect ratio, as a number or a `width:height` ratio such as `16:9`
+    #[arg(long, value_parser = parse_aspect_ratio)]
+    aspect: Option<f64>,
+
+    /// Number of samples per pixel
+    #[arg(long)]
+    spp: Option<usize>,
+
+    /// Source of the sample values within pixels
+    #[arg(long, value_enum)]
+    sampler: Option<SamplerKind>,
+
+    /// Pixel reconstruction filter
+    #[arg(long, value_enum)]
+    filter: Option<FilterKind>,
+
+    /// Radius of the reconstruction filter in pixels, defaulting to one suited to the filter
+    #[arg(long, requires = "filter")]
+    filter_radius: Option<f64>,
+
+    /// Weighting of light sampling against BSDF sampling
+    #[arg(long, value_enum, default_value_t = MisKind::Power)]
+    mis: MisKind,
+
+    /// Maximum number of ray bounces
+    #[arg(long)]
+    max_depth: Option<usize>,
+
+    /// Number of ray bounces after which paths are terminated by Russian roulette
+    #[arg(long)]
+    roulette_depth: Option<usize>,
+
+    /// Seed for the scene generation and the pixel samples
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Number of render threads, 0 meaning one per available core
+    #[arg(long, default_value_t = 0)]
+    threads: usize,
+
+    /// Output image path, its extension selecting the format (ppm, png, exr, hdr, pfm); standard
+    /// output if not given
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Output image format, overriding the one selected by the output path extension, such as
+    /// exr32 for a 32-bit float OpenEXR instead of a half float one
+    #[arg(long, value_enum)]
+    format: Option<OutputFormat>,
+
+    /// Exposure adjustment in stops, applied before tone mapping
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    exposure: f64,
+
+    /// Tone mapping operator of low dynamic range outputs
+    #[arg(long, value_enum, default_value_t = ToneMapOperator::Clamp)]
+    tone_map: ToneMapOperator,
+
+    /// Radiance mapped to white by the reinhard and hable operators
+    #[arg(long)]
+    white: Option<f64>,
+
+    /// Encode with a plain power law of this gamma instead of the sRGB transfer function
+    #[arg(long)]
+    gamma: Option<f64>,
+
+    /// Dithering of low dynamic range outputs
+    #[arg(long, value_enum, default_value_t = DitherMode::None)]
+    dither: DitherMode,
+
+    /// Number of samples per pixel added by each rendering pass
+    #[arg(long, default_value_t = 4, value_parser = clap::value_parser!(u32).range(1..))]
+    pass_spp: u32,
+
+    /// Stop sampling pixels once the standard error of their mean luminance relative to the mean
+    /// is below this threshold, `--spp` becoming the maximum number of samples per pixel
+    #[arg(long, value_name = "THRESHOLD")]
+    adaptive: Option<f64>,
+
+    /// Minimum number of samples per pixel with adaptive sampling
+    #[arg(long, default_value_t = 16)]
+    min_spp: usize,
+
+    /// Image of the number of samples spent on each pixel, white being the maximum
+    #[arg(long, value_name = "FILE")]
+    samples_output: Option<PathBuf>,
+
+    /// Stop rendering after the pass during which this many seconds have elapsed
+    #[arg(long, value_name = "SECONDS", value_parser = parse_duration)]
+    time_budget: Option<Duration>,
+
+    /// Checkpoint file, saved periodically and at the end of the render
+    #[arg(long, value_name = "FILE")]
+    checkpoint: Option<PathBuf>,
+
+    /// Minimum number of seconds between two checkpoint saves
+    #[arg(long, value_name = "SECONDS", default_value = "60", value_parser = parse_duration)]
+    checkpoint_interval: Duration,
+
+    /// Continue the render saved in the checkpoint file up to the number of samples per pixel
+    #[arg(long, requires = "checkpoint")]
+    resume: bool,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum OutputFormat {
+    /// ASCII PPM
+    P3,
+
+    /// Binary PPM
+    P6,
+
+    /// 8-bit PNG
+    Png,
+
+    /// 16-bit PNG
+    Png16,
+
+    /// Half float OpenEXR
+    Exr,
+
+    /// 32-bit float OpenEXR
+    Exr32,
+
+    /// Radiance RGBE
+    Hdr,
+
+    /// Portable float map
+    Pfm,
+}
+
+impl OutputFormat {
+    fn writer(self, display: DisplayTransform) -> Box<dyn ImageWriter> {
+        match self {
+            OutputFormat::P3 => Box::new(PpmWriter::ascii().with_display(display)),
+            OutputFormat::P6 => Box::new(PpmWriter::binary().with_display(display)),
+            OutputFormat::Png => Box::new(PngWriter::new(BitDepth::Eight).with_display(display)),
+            OutputFormat::Png16 => {
+                Box::new(PngWriter::new(BitDepth::Sixteen).with_display(display))
+            },
+            OutputFormat::Exr => Box::new(ExrWriter::new(Precision::Half)),
+            OutputFormat::Exr32 => Box::new(ExrWriter::new(Precision::Float)),
+            OutputFormat::Hdr => Box::new(HdrWriter),
+            OutputFormat::Pfm => Box::new(PfmWriter),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum SamplerKind {
+    /// Independent uniform random values
+    Independent,
+
+    /// Jittered values in shuffled strata
+    Stratified,
+
+    /// Randomly shifted Halton sequence
+    Halton,
+
+    /// Owen scrambled Sobol sequence
+    Sobol,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum FilterKind {
+    /// Constant weight, over one pixel by default
+    Box,
+
+    /// Linearly decreasing weight
+    Tent,
+
+    /// Truncated Gaussian
+    Gaussian,
+
+    /// Mitchell-Netravali cubic
+    Mitchell,
+
+    /// Lanczos windowed sinc
+    Lanczos,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum MisKind {
+    /// Balance heuristic
+    Balance,
+
+    /// Power heuristic with an exponent of 2
+    Power,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum ToneMapOperator {
+    /// Clip radiance above 1
+    Clamp,
+
+    /// Extended Reinhard on luminance
+    Reinhard,
+
+    /// Hable's Uncharted 2 filmic curve
+    Hable,
+
+    /// ACES fitted curve
+    Aces,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum DitherMode {
+    /// No dithering
+    None,
+
+    /// Bayer matrix
+    Ordered,
+
+    /// Blue noise mask
+    BlueNoise,
+}
+
+/// Build the conversion of the rendered radiance for low dynamic range outputs.
+fn display_transform(cli: &Cli) -> DisplayTransform {
+    let display = match cli.tone_map {
+        ToneMapOperator::Clamp => DisplayTransform::default().with_tone_map(Clamp),
+        ToneMapOperator::Reinhard => DisplayTransform::default()
+            .with_tone_map(cli.white.map_or_else(Reinhard::default, Reinhard::new)),
+        ToneMapOperator::Hable => DisplayTransform::default()
+            .with_tone_map(cli.white.map_or_else(Hable::default, Hable::new)),
+        ToneMapOperator::Aces => DisplayTransform::default().with_tone_map(Aces),
+    };
+
+    let transfer = cli.gamma.map_or(Transfer::Srgb, Transfer::Gamma);
+    let dither = match cli.dither {
+        DitherMode::None => Dither::None,
+        DitherMode::Ordered => Dither::Ordered,
+        DitherMode::BlueNoise => Dither::BlueNoise,
+    };
+
+    display
+        .with_exposure(cli.exposure)
+        .with_transfer(transfer)
+        .with_dither(dither)
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        },
+    }
+}
+
+fn run(cli: Cli) -> Result<(), Box<dyn Error>> {
     let mut log = Logger::new(io::stderr());
 
-    let image_width = 1200;
-    let image_height = (image_width as f64 / ASPECT_RATIO) as usize;
-    let camera = final_scene_camera(image_width, image_height);
-    let mut renderer = Renderer::new(camera, image_width, image_height)
-        .with_samples_per_pixel(100)
-        .with_max_depth(50);
+    let display = display_transform(&cli);
+    let writer = match (cli.format, &cli.output) {
+        (Some(format), _) => format.writer(display),
+        (None, Some(path)) => output::writer_for_path(path, &display)?,
+        (None, None) => OutputFormat::P3.writer(display),
+    };
 
-    let world = final_scene_world();
+    // Sample counts are mapped linearly to gray levels.
+    let samples_writer = match &cli.samples_output {
+        Some(path) => {
+            let display = DisplayTransform::default().with_transfer(Transfer::Gamma(1.0));
+            Some((path, output::writer_for_path(path, &display)?))
+        },
+        None => None,
+    };
 
-    log.msg("Render frame ").flush();
+    let scene = load_scene(&cli)?;
+    let mut renderer = scene
+        .renderer
+        .with_threads(cli.threads)?
+        .with_samples_per_pass(cli.pass_spp as usize);
+    if let Some(spp) = cli.spp {
+        renderer = renderer.with_samples_per_pixel(spp);
+    }
+    renderer = match cli.sampler {
+        Some(SamplerKind::Independent) => renderer.with_sampler(Independent),
+        Some(SamplerKind::Stratified) => {
+            let samples_per_pixel = renderer.samples_per_pixel();
+            renderer.with_sampler(Stratified::new(samples_per_pixel))
+        },
+        Some(SamplerKind::Halton) => renderer.with_sampler(Halton),
+        Some(SamplerKind::Sobol) => renderer.with_sampler(Sobol),
+        None => renderer,
+    };
+    let radius = cli.filter_radius;
+    renderer = match cli.filter {
+        Some(FilterKind::Box) => {
+            renderer.with_filter(radius.map_or_else(BoxFilter::default, BoxFilter::new))
+        },
+        Some(FilterKind::Tent) => {
+            renderer.with_filter(radius.map_or_else(TentFilter::default, TentFilter::new))
+        },
+        Some(FilterKind::Gaussian) => {
+            renderer.with_filter(radius.map_or_else(GaussianFilter::default, |radius| {
+                GaussianFilter::new(radius, radius / 3.0)
+            }))
+        },
+        Some(FilterKind::Mitchell) => {
+            renderer.with_filter(radius.map_or_else(MitchellFilter::default, |radius| {
+                MitchellFilter::new(radius, 1.0 / 3.0, 1.0 / 3.0)
+            }))
+        },
+        Some(FilterKind::Lanczos) => {
+            renderer.with_filter(radius.map_or_else(LanczosFilter::default, LanczosFilter::new))
+        },
+        None => renderer,
+    };
+    if let Some(threshold) = cli.adaptive {
+        renderer = renderer.with_adaptive_sampling(AdaptiveSampling {
+            min_samples: cli.min_spp,
+            threshold,
+        });
+    }
+    if let Some(max_depth) = cli.max_depth {
+        renderer = renderer.with_max_depth(max_depth);
+    }
+    if let Some(roulette_depth) = cli.roulette_depth {
+        renderer = renderer.with_roulette_depth(roulette_depth);
+    }
+    renderer = renderer.with_mis_heuristic(match cli.mis {
+        MisKind::Balance => MisHeuristic::Balance,
+        MisKind::Power => MisHeuristic::Power,
+    });
+    if let Some(seed) = cli.seed {
+        renderer = renderer.with_seed(seed);
+    }
+    if cli.width.is_some() || cli.height.is_some() || cli.aspect.is_some() {
+        let (image_width, image_height) =
+            image_size(&cli, renderer.image_width(), renderer.image_height())?;
+        renderer = renderer.with_image_size(image_width, image_height);
+    }
 
-    let timer = renderer.render(world.as_slice(), Fading::<2>::Const(0.98));
+    log.msg("Build BVH ").flush();
+
+    let mut timer = Timer::start();
+    let world = Bvh::new(scene.world);
+    timer.stop();
     log.elapsed(&timer).ln();
+    log.msg(world.stats()).ln();
+
+    let scene_hash = scene_hash(&cli, &renderer)?;
+    if let (true, Some(path)) = (cli.resume, &cli.checkpoint) {
+        renderer.resume(Checkpoint::load(path)?, scene_hash)?;
+        log.msg(format!(
+            "Resume from {} with {:.1} samples per pixel on average",
+            path.display(),
+            renderer.accumulator().mean_samples()
+        ))
+        .ln();
+    }
+
+    log.msg("Render frame ").flush();
+
+    let timer = render(&cli, &mut renderer, &world, scene.fading, scene_hash)?;
+    log.elapsed(&timer)
+        .msg(format!(
+            " {:.1} samples per pixel on average.",
+            renderer.accumulator().mean_samples()
+        ))
+        .ln();
 
     log.msg("Output image ").flush();
 
-    let timer = renderer.output();
+    let mut timer = Timer::start();
+    let frame = renderer.frame();
+    match &cli.output {
+        Some(path) => writer.write_to_path(&frame, path)?,
+        None => writer.write(&frame, &mut io::stdout().lock())?,
+    }
+    if let Some((path, writer)) = &samples_writer {
+        let scale = 1.0 / renderer.samples_per_pixel().max(1) as f64;
+        writer.write_to_path(&renderer.accumulator().samples_frame(scale), path)?;
+    }
+    timer.stop();
     log.elapsed(&timer).ln();
+
+    Ok(())
+}
+
+/// Render in passes until every pixel has the requested number of samples or the time budget is
+/// spent, saving checkpoints along the way if asked to.
+fn render(
+    cli: &Cli,
+    renderer: &mut Renderer,
+    world: &impl Hittable,
+    fading: Fading,
+    scene_hash: u64,
+) -> Result<Timer, Box<dyn Error>> {
+    let mut timer = Timer::start();
+    let start = Instant::now();
+    let mut last_checkpoint = Instant::now();
+
+    loop {
+        let out_of_time = cli
+            .time_budget
+            .is_some_and(|budget| start.elapsed() >= budget);
+        if renderer.is_complete() || out_of_time {
+            break;
+        }
+
+        renderer.render_pass(world, fading);
+
+        if let Some(path) = &cli.checkpoint {
+            if last_checkpoint.elapsed() >= cli.checkpoint_interval {
+                renderer.checkpoint(scene_hash).save(path)?;
+                last_checkpoint = Instant::now();
+            }
+        }
+    }
+
+    if let Some(path) = &cli.checkpoint {
+        renderer.checkpoint(scene_hash).save(path)?;
+    }
+
+    timer.stop();
+    Ok(timer)
+}
+
+/// Hash of the scene and of the settings the samples depend on, which a checkpoint must match to
+/// be resumed. Scene files are hashed by content, but not the files they refer to.
+fn scene_hash(cli: &Cli, renderer: &Renderer) -> Result<u64, Box<dyn Error>> {
+    let mut hasher = SceneHasher::default();
+
+    if BUILTIN_SCENES.iter().any(|(name, _)| *name == cli.scene) {
+        hasher.write(cli.scene.as_bytes());
+    } else {
+        hasher.write(&fs::read(&cli.scene)?);
+    }
+
+    // Only the kind of sampler is hashed, the stratified one depending on the sample count as
+    // well, so that a run can be resumed with a higher `--spp`. Its later samples then come from
+    // a larger grid, which keeps each of them uniformly distributed.
+    hasher.write(format!("{:?}", cli.sampler).as_bytes());
+    hasher.write(format!("{:?}", renderer.filter()).as_bytes());
+    hasher.write(format!("{:?}", renderer.mis_heuristic()).as_bytes());
+    for value in [
+        renderer.image_width() as u64,
+        renderer.image_height() as u64,
+        renderer.max_depth() as u64,
+        renderer.roulette_depth() as u64,
+        renderer.seed(),
+    ] {
+        hasher.write_u64(value);
+    }
+
+    Ok(hasher.finish())
+}
+
+fn load_scene(cli: &Cli) -> Result<Scene, Box<dyn Error>> {
+    let builtin = BUILTIN_SCENES
+        .iter()
+        .find(|(name, _)| *name == cli.scene.as_str());
+
+    match builtin {
+        Some((_, scene)) => {
+            let seed = cli.seed.unwrap_or_default();
+            Ok(scene(&mut random::seeded(seed)))
+        },
+        None if Path::new(&cli.scene).is_file() => Ok(Scene::load(&cli.scene)?),
+        None => {
+            let names: Vec<_> = BUILTIN_SCENES.iter().map(|(name, _)| *name).collect();
+            Err(format!(
+                "no built-in scene or scene file named `{}`, built-in scenes are: {}",
+                cli.scene,
+                names.join(", ")
+            )
+            .into())
+        },
+    }
+}
+
+/// Resolve the image size from the command line, falling back to the size set by the scene.
+fn image_size(
+    cli: &Cli,
+    scene_width: usize,
+    scene_height: usize,
+) -> Result<(usize, usize), Box<dyn Error>> {
+    let aspect_ratio = cli
+        .aspect
+        .unwrap_or(scene_width as f64 / scene_height as f64);
+
+    let (image_width, image_height) = match (cli.width, cli.height) {
+        (Some(width), Some(height)) => (width, height),
+        (Some(width), None) => (width, (width as f64 / aspect_ratio) as usize),
+        (None, Some(height)) => ((height as f64 * aspect_ratio) as usize, height),
+        (None, None) => (scene_width, (scene_width as f64 / aspect_ratio) as usize),
+    };
+
+    if image_width == 0 || image_height == 0 {
+        return Err(format!("invalid image size {image_width}x{image_height}").into());
+    }
+
+    Ok((image_width, image_height))
+}
+
+fn parse_aspect_ratio(value: &str) -> Result<f64, String> {
+    let aspect_ratio = match value.split_once(':') {
+        Some((width, height)) => {
+            let width: f64 = width
+                .parse()
+                .map_err(|_| format!("invalid width `{width}`"))?;
+            let height: f64 = height
+                .parse()
+                .map_err(|_| format!("invalid height `{height}`"))?;
+            width / height
+        },
+        None => value
+            .parse()
+            .map_err(|_| format!("invalid aspect ratio `{value}`"))?,
+    };
+
+    if aspect_ratio.is_finite() && aspect_ratio > 0.0 {
+        Ok(aspect_ratio)
+    } else {
+        Err(format!("aspect ratio must be positive, got `{value}`"))
+    }
+}
+
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    value
+        .parse()
+        .ok()
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+        .ok_or_else(|| format!("invalid number of seconds `{value}`"))
+}
+
+fn final_scene(rng: &mut Rng) -> Scene {
+    let camera = final_scene_camera(IMAGE_WIDTH, IMAGE_HEIGHT);
+
+    Scene {
+        renderer: Renderer::new(camera, IMAGE_WIDTH, IMAGE_HEIGHT)
+            .with_samples_per_pixel(100)
+            .with_max_depth(50),
+        world: final_scene_world(rng, false),
+        fading: Fading::Const(0.98),
+    }
+}
+
+/// The final scene with the small diffuse spheres bouncing up during the shutter interval.
+fn bouncing_scene(rng: &mut Rng) -> Scene {
+    let camera = final_scene_camera(IMAGE_WIDTH, IMAGE_HEIGHT).with_shutter(0.0, 1.0);
+
+    Scene {
+        renderer: Renderer::new(camera, IMAGE_WIDTH, IMAGE_HEIGHT)
+            .with_samples_per_pixel(100)
+            .with_max_depth(50),
+        world: final_scene_world(rng, true),
+        fading: Fading::Const(0.98),
+    }
+}
+
+fn simple_scene(_rng: &mut Rng) -> Scene {
+    let camera = simple_scene_camera(IMAGE_WIDTH, IMAGE_HEIGHT);
+
+    Scene {
+        renderer: Renderer::new(camera, IMAGE_WIDTH, IMAGE_HEIGHT)
+            .with_samples_per_pixel(100)
+            .with_max_depth(50),
+        world: simple_scene_world().into(),
+        fading: Fading::Const(0.98),
+    }
+}
+
+/// The Cornell box, in a square image by default.
+fn cornell_scene(_rng: &mut Rng) -> Scene {
+    let camera = Camera::new(
+        Point3::new(278.0, 278.0, -800.0),
+        Point3::new(278.0, 278.0, 0.0),
+    )
+    .with_vertical_fov(40.0)
+    .with_viewport_size(IMAGE_WIDTH, IMAGE_WIDTH);
+    let light = Quad::new(
+        Point3::new(343.0, 554.0, 332.0),
+        Vector3::new(-130.0, 0.0, 0.0),
+        Vector3::new(0.0, 0.0, -105.0),
+        DiffuseLight::new(Color::new(15.0, 15.0, 15.0)),
+    );
+
+    Scene {
+        renderer: Renderer::new(camera, IMAGE_WIDTH, IMAGE_WIDTH)
+            .with_samples_per_pixel(100)
+            .with_max_depth(50)
+            .with_background(Background::Solid(Color::BLACK))
+            .with_lights(vec![Box::new(light.clone())]),
+        world: cornell_scene_world(light),
+        fading: Fading::Const(1.0),
+    }
 }
 
 fn final_scene_camera(image_width: usize, image_height: usize) -> Camera {
@@ -51,7 +666,7 @@ fn final_scene_camera(image_width: usize, image_height: usize) -> Camera {
         .with_viewport_size(image_width, image_height)
 }
 
-fn final_scene_world() -> Vec<Box<dyn Hittable>> {
+fn final_scene_world(rng: &mut Rng, bouncing: bool) -> Vec<Box<dyn Hittable>> {
     let mut world: Vec<Box<dyn Hittable>> = Vec::new();
 
     let ground = Sphere::new(
@@ -63,22 +678,29 @@ fn final_scene_world() -> Vec<Box<dyn Hittable>> {
 
     for a in -11..11 {
         for b in -11..11 {
-            let choose_mat = rand::random::<f64>();
+            let choose_mat = rng.random::<f64>();
             let center = Point3::new(
-                a as f64 + 0.9 * rand::random::<f64>(),
+                a as f64 + 0.9 * rng.random::<f64>(),
                 0.2,
-                b as f64 + 0.9 * rand::random::<f64>(),
+                b as f64 + 0.9 * rng.random::<f64>(),
             );
 
             if (center - Point3::new(4.0, 0.2, 0.0)).magnitude() > 0.9 {
                 if choose_mat < 0.8 {
                     // diffuse
-                    let albedo = Color::random() * Color::random();
-                    world.push(Box::new(Sphere::new(center, 0.2, Lambertian::new(albedo))));
+                    let albedo = Color::random(rng) * Color::random(rng);
+                    let material = Lambertian::new(albedo);
+                    if bouncing {
+                        let end = center + Vector3::new(0.0, rng.random_range(0.0..0.5), 0.0);
+                        let motion = Motion::linear(center, end);
+                        world.push(Box::new(Sphere::moving(motion, 0.2, material)));
+                    } else {
+                        world.push(Box::new(Sphere::new(center, 0.2, material)));
+                    }
                 } else if choose_mat < 0.95 {
                     // metal
-                    let albedo = Color::random_range(0.5..=1.0);
-                    let fuzz = rand::random_range(0.0..=0.5);
+                    let albedo = Color::random_range(0.5..=1.0, rng);
+                    let fuzz = rng.random_range(0.0..=0.5);
                     world.push(Box::new(Sphere::new(center, 0.2, Metal::new(albedo, fuzz))));
                 } else {
                     // glass
@@ -108,6 +730,73 @@ fn final_scene_world() -> Vec<Box<dyn Hittable>> {
     world
 }
 
+fn cornell_scene_world(light: impl Hittable + 'static) -> Vec<Box<dyn Hittable>> {
+    let red = Lambertian::new(Color::new(0.65, 0.05, 0.05));
+    let white = Lambertian::new(Color::new(0.73, 0.73, 0.73));
+    let green = Lambertian::new(Color::new(0.12, 0.45, 0.15));
+
+    let wall = |q: [f64; 3], u: [f64; 3], v: [f64; 3], material: &Lambertian| {
+        let quad = Quad::new(
+            Point3::from(q),
+            Vector3::from(u),
+            Vector3::from(v),
+            material.clone(),
+        );
+        Box::new(quad) as Box<dyn Hittable>
+    };
+
+    // Boxes standing on the floor at the origin, turned and moved into place
+    let tall_box = make_box(
+        Point3::zeros(),
+        Point3::new(165.0, 330.0, 165.0),
+        white.clone(),
+    );
+    let short_box = make_box(
+        Point3::zeros(),
+        Point3::new(165.0, 165.0, 165.0),
+        white.clone(),
+    );
+
+    vec![
+        wall(
+            [555.0, 0.0, 0.0],
+            [0.0, 555.0, 0.0],
+            [0.0, 0.0, 555.0],
+            &green,
+        ),
+        wall([0.0, 0.0, 0.0], [0.0, 555.0, 0.0], [0.0, 0.0, 555.0], &red),
+        wall(
+            [0.0, 0.0, 0.0],
+            [555.0, 0.0, 0.0],
+            [0.0, 0.0, 555.0],
+            &white,
+        ),
+        wall(
+            [555.0, 555.0, 555.0],
+            [-555.0, 0.0, 0.0],
+            [0.0, 0.0, -555.0],
+            &white,
+        ),
+        wall(
+            [0.0, 0.0, 555.0],
+            [555.0, 0.0, 0.0],
+            [0.0, 555.0, 0.0],
+            &white,
+        ),
+        Box::new(light),
+        Box::new(
+            Instance::new(Arc::new(tall_box), Affine3::identity())
+                .rotated(Vector3::y(), 15.0)
+                .translated(Vector3::new(265.0, 0.0, 295.0)),
+        ),
+        Box::new(
+            Instance::new(Arc::new(short_box), Affine3::identity())
+                .rotated(Vector3::y(), -18.0)
+                .translated(Vector3::new(130.0, 0.0, 65.0)),
+        ),
+    ]
+}
+
 fn simple_scene_camera(image_width: usize, image_height: usize) -> Camera {
     Camera::new(Point3::new(-2.0, 2.0, 1.0), Point3::new(0.0, 0.0, -1.0))
         .with_defocus_angle(10.0)
@@ -1,64 +1,220 @@
+use std::f64::consts::PI;
 use std::fmt;
+use std::ops::Deref;
 
 use crate::object::Hit;
-use crate::types::{near_zero, random_unit_vector_on_sphere, Color, Ray, Vector3};
+use crate::texture::{SolidColor, Texture};
+use crate::types::{near_zero, uniform_sphere_sample, Color, Point2, Vector3};
 
-pub trait Material: fmt::Debug {
-    fn scatter(&self, ray: &Ray, hit: &Hit) -> Option<(Ray, Color)>;
+/// Direction sampled by a material, along with what is needed to weight the light it brings.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BsdfSample {
+    /// Unit direction the light is gathered from.
+    pub direction: Vector3,
+
+    /// BSDF value times the cosine factor over the probability density of the direction, by
+    /// which the light coming from the direction is multiplied.
+    pub weight: Color,
+
+    /// Probability density of the direction, with respect to solid angle. Not meaningful for
+    /// delta lobes.
+    pub pdf: f64,
+
+    /// Whether the direction comes from a delta lobe, such as a perfect mirror, which only
+    /// scatters into discrete directions and cannot be evaluated or combined with light
+    /// sampling.
+    pub delta: bool,
+}
+
+/// Scattering of light at a surface, described by its bidirectional scattering distribution
+/// function (BSDF).
+///
+/// Directions are unit vectors pointing away from the hit point: `wo` towards where the light
+/// goes, usually the opposite of the incoming ray direction, and `wi` towards where it comes from.
+pub trait Material: fmt::Debug + Send + Sync {
+    /// BSDF value for light arriving from `wi` and leaving along `wo`, without the cosine
+    /// factor. Delta lobes are not included.
+    fn f(&self, _hit: &Hit, _wo: &Vector3, _wi: &Vector3) -> Color {
+        Color::BLACK
+    }
+
+    /// Probability density with respect to solid angle of `sample` returning `wi`.
+    fn pdf(&self, _hit: &Hit, _wo: &Vector3, _wi: &Vector3) -> f64 {
+        0.0
+    }
+
+    /// Sample a direction the light leaving along `wo` comes from, using the 2D sample `u`.
+    /// Returns `None` when the light is absorbed.
+    fn sample(&self, _hit: &Hit, _wo: &Vector3, _u: Point2) -> Option<BsdfSample> {
+        None
+    }
+
+    /// Whether the material emits light, and objects made of it should be sampled as lights.
+    fn is_emissive(&self) -> bool {
+        false
+    }
+
+    /// Light emitted by the material at the hit point, black for materials which are not light
+    /// sources.
+    fn emitted(&self, _hit: &Hit) -> Color {
+        Color::BLACK
+    }
+}
+
+impl<T> Material for T
+where
+    T: Deref<Target = dyn Material> + fmt::Debug + Send + Sync,
+{
+    fn f(&self, hit: &Hit, wo: &Vector3, wi: &Vector3) -> Color {
+        self.deref().f(hit, wo, wi)
+    }
+
+    fn pdf(&self, hit: &Hit, wo: &Vector3, wi: &Vector3) -> f64 {
+        self.deref().pdf(hit, wo, wi)
+    }
+
+    fn sample(&self, hit: &Hit, wo: &Vector3, u: Point2) -> Option<BsdfSample> {
+        self.deref().sample(hit, wo, u)
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.deref().is_emissive()
+    }
+
+    fn emitted(&self, hit: &Hit) -> Color {
+        self.deref().emitted(hit)
+    }
 }
 
 #[derive(Clone, Debug)]
-pub struct Lambertian {
-    albedo: Color,
+pub struct Lambertian<T = SolidColor> {
+    albedo: T,
 }
 
 impl Lambertian {
     pub fn new(albedo: Color) -> Self {
+        Self::from_texture(SolidColor::new(albedo))
+    }
+}
+
+impl<T: Texture> Lambertian<T> {
+    pub fn from_texture(albedo: T) -> Self {
         Self { albedo }
     }
 }
 
-impl Material for Lambertian {
-    fn scatter(&self, _ray: &Ray, hit: &Hit) -> Option<(Ray, Color)> {
-        let mut scatter_direction = hit.normal + random_unit_vector_on_sphere();
+impl<T: Texture> Material for Lambertian<T> {
+    fn f(&self, hit: &Hit, _wo: &Vector3, wi: &Vector3) -> Color {
+        if wi.dot(&hit.normal) > 0.0 {
+            (1.0 / PI) * self.albedo.value(&hit.uv, &hit.point)
+        } else {
+            Color::BLACK
+        }
+    }
+
+    fn pdf(&self, hit: &Hit, _wo: &Vector3, wi: &Vector3) -> f64 {
+        wi.dot(&hit.normal).max(0.0) / PI
+    }
+
+    /// Cosine weighted direction, as the normal offset by a point of the unit sphere.
+    fn sample(&self, hit: &Hit, wo: &Vector3, u: Point2) -> Option<BsdfSample> {
+        let mut direction = hit.normal + uniform_sphere_sample(u);
 
         // Catch degenerate scatter direction
-        if near_zero(&scatter_direction) {
-            scatter_direction = hit.normal;
+        if near_zero(&direction) {
+            direction = hit.normal;
         }
 
-        let scattered = Ray::new(hit.point, scatter_direction);
-        let attenuation = self.albedo;
-
-        Some((scattered, attenuation))
+        let direction = direction.normalize();
+        Some(BsdfSample {
+            direction,
+            weight: self.albedo.value(&hit.uv, &hit.point),
+            pdf: self.pdf(hit, wo, &direction),
+            delta: false,
+        })
     }
 }
 
 #[derive(Clone, Debug)]
-pub struct Metal {
-    albedo: Color,
+pub struct Metal<T = SolidColor> {
+    albedo: T,
     fuzz: f64,
 }
 
 impl Metal {
     pub fn new(albedo: Color, fuzz: f64) -> Self {
+        Self::from_texture(SolidColor::new(albedo), fuzz)
+    }
+}
+
+impl<T: Texture> Metal<T> {
+    pub fn from_texture(albedo: T, fuzz: f64) -> Self {
         Self {
             albedo,
             fuzz: fuzz.clamp(0.0, 1.0),
         }
     }
+
+    /// Probability density of the direction `wi` towards a point picked uniformly on the sphere
+    /// of radius `fuzz` around the tip of the unit `reflected` direction.
+    ///
+    /// The ray along `wi` crosses the sphere at distances `t` where `t² - 2bt + 1 - fuzz² = 0`,
+    /// with `b = wi·reflected`, and each crossing contributes the density of the sphere area,
+    /// `1 / (4π fuzz²)`, times `t²` over the cosine `√(b² - 1 + fuzz²) / fuzz` between the ray and
+    /// the sphere.
+    fn fuzz_pdf(&self, reflected: &Vector3, wi: &Vector3) -> f64 {
+        let b = wi.dot(reflected);
+        let discriminant = b * b - (1.0 - self.fuzz * self.fuzz);
+        if discriminant <= 0.0 {
+            return 0.0;
+        }
+
+        let sqrt = discriminant.sqrt();
+        [b - sqrt, b + sqrt]
+            .into_iter()
+            .filter(|&t| t > 0.0)
+            .map(|t| t * t / (4.0 * PI * self.fuzz * sqrt))
+            .sum()
+    }
 }
 
-impl Material for Metal {
-    fn scatter(&self, ray: &Ray, hit: &Hit) -> Option<(Ray, Color)> {
-        let reflected = reflect(&ray.direction, &hit.normal);
-        let reflected = reflected.normalize() + self.fuzz * random_unit_vector_on_sphere();
+impl<T: Texture> Material for Metal<T> {
+    fn f(&self, hit: &Hit, wo: &Vector3, wi: &Vector3) -> Color {
+        let cosine = wi.dot(&hit.normal);
+        if self.fuzz == 0.0 || cosine <= 0.0 {
+            return Color::BLACK;
+        }
+
+        let reflected = reflect(&-wo, &hit.normal);
+        (self.fuzz_pdf(&reflected, wi) / cosine) * self.albedo.value(&hit.uv, &hit.point)
+    }
+
+    fn pdf(&self, hit: &Hit, wo: &Vector3, wi: &Vector3) -> f64 {
+        if self.fuzz == 0.0 || wi.dot(&hit.normal) <= 0.0 {
+            return 0.0;
+        }
+
+        self.fuzz_pdf(&reflect(&-wo, &hit.normal), wi)
+    }
 
-        if reflected.dot(&hit.normal) > 0.0 {
-            let scattered = Ray::new(hit.point, reflected);
-            let attenuation = self.albedo;
+    /// Mirror direction offset by a point of the sphere of radius `fuzz`, a delta lobe without
+    /// fuzz. Directions below the surface are absorbed.
+    fn sample(&self, hit: &Hit, wo: &Vector3, u: Point2) -> Option<BsdfSample> {
+        let reflected = reflect(&-wo, &hit.normal);
+        let direction = (reflected + self.fuzz * uniform_sphere_sample(u)).normalize();
 
-            Some((scattered, attenuation))
+        if direction.dot(&hit.normal) > 0.0 {
+            let delta = self.fuzz == 0.0;
+            Some(BsdfSample {
+                direction,
+                weight: self.albedo.value(&hit.uv, &hit.point),
+                pdf: if delta {
+                    1.0
+                } else {
+                    self.fuzz_pdf(&reflected, &direction)
+                },
+                delta,
+            })
         } else {
             None
         }
@@ -79,29 +235,60 @@ impl Dielectric {
 }
 
 impl Material for Dielectric {
-    fn scatter(&self, ray: &Ray, hit: &Hit) -> Option<(Ray, Color)> {
-        let attenuation = Color::WHITE;
+    /// Reflection or refraction, a pair of delta lobes picked with the probability of the
+    /// reflectance.
+    fn sample(&self, hit: &Hit, wo: &Vector3, u: Point2) -> Option<BsdfSample> {
         let refraction_index = if hit.front_face {
             1.0 / self.refraction_index
         } else {
             self.refraction_index
         };
 
-        let unit_direction = ray.direction.normalize();
-        let cos_theta = (-unit_direction).dot(&hit.normal).min(1.0);
+        let unit_direction = -wo;
+        let cos_theta = wo.dot(&hit.normal).min(1.0);
         let sin_theta = (1.0 - cos_theta.powi(2)).sqrt();
 
         let cannot_refract = refraction_index * sin_theta > 1.0;
-        let direction =
-            if cannot_refract || reflectance(cos_theta, refraction_index) > rand::random::<f64>() {
-                reflect(&unit_direction, &hit.normal)
-            } else {
-                refract(&unit_direction, &hit.normal, refraction_index)
-            };
+        let direction = if cannot_refract || reflectance(cos_theta, refraction_index) > u.x {
+            reflect(&unit_direction, &hit.normal)
+        } else {
+            refract(&unit_direction, &hit.normal, refraction_index)
+        };
+
+        Some(BsdfSample {
+            direction: direction.normalize(),
+            weight: Color::WHITE,
+            pdf: 1.0,
+            delta: true,
+        })
+    }
+}
+
+/// Light emitting material, which does not reflect any light.
+#[derive(Clone, Debug)]
+pub struct DiffuseLight<T = SolidColor> {
+    emit: T,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Color) -> Self {
+        Self::from_texture(SolidColor::new(emit))
+    }
+}
 
-        let scattered = Ray::new(hit.point, direction);
+impl<T: Texture> DiffuseLight<T> {
+    pub fn from_texture(emit: T) -> Self {
+        Self { emit }
+    }
+}
+
+impl<T: Texture> Material for DiffuseLight<T> {
+    fn is_emissive(&self) -> bool {
+        true
+    }
 
-        Some((scattered, attenuation))
+    fn emitted(&self, hit: &Hit) -> Color {
+        self.emit.value(&hit.uv, &hit.point)
     }
 }
 
@@ -0,0 +1,60 @@
+//! Positions of objects changing over time, blurred across the shutter interval of the camera.
+
+use crate::aabb::Aabb;
+use crate::types::Point3;
+
+/// Position of an object over time.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Motion {
+    /// Position which does not change.
+    Fixed(Point3),
+
+    /// Positions at increasing times, linearly interpolated in between, and held before the
+    /// first one and after the last one.
+    Keyframes(Vec<(f64, Point3)>),
+}
+
+impl Motion {
+    /// Linear motion from `start` at time 0 to `end` at time 1.
+    pub fn linear(start: Point3, end: Point3) -> Self {
+        Motion::Keyframes(vec![(0.0, start), (1.0, end)])
+    }
+
+    /// Motion through the keyframes, given as times and positions in any order.
+    ///
+    /// NOTE: there is assumed to be at least one keyframe.
+    pub fn keyframes(mut keyframes: Vec<(f64, Point3)>) -> Self {
+        keyframes.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+        Motion::Keyframes(keyframes)
+    }
+
+    pub fn at(&self, time: f64) -> Point3 {
+        let keyframes = match self {
+            Motion::Fixed(position) => return *position,
+            Motion::Keyframes(keyframes) => keyframes,
+        };
+
+        // Index of the first keyframe after the time
+        let next = keyframes.partition_point(|(key_time, _)| *key_time <= time);
+        if next == 0 {
+            return keyframes[0].1;
+        } else if next == keyframes.len() {
+            return keyframes[next - 1].1;
+        }
+
+        let (start_time, start) = keyframes[next - 1];
+        let (end_time, end) = keyframes[next];
+        let s = (time - start_time) / (end_time - start_time);
+        start + s * (end - start)
+    }
+
+    /// Box enclosing every position of the motion, which stays within the keyframes' one.
+    pub fn bounding_box(&self) -> Aabb {
+        match self {
+            Motion::Fixed(position) => Aabb::new(*position, *position),
+            Motion::Keyframes(keyframes) => keyframes
+                .iter()
+                .fold(Aabb::EMPTY, |bbox, (_, position)| bbox.grow(position)),
+        }
+    }
+}
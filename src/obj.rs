@@ -0,0 +1,473 @@
+//! Loader for Wavefront OBJ models and their MTL material libraries.
+//!
+//! MTL materials are mapped onto the renderer materials as follows:
+//! - emissive materials (non-black `Ke`) become [`DiffuseLight`] emitting `Ke`,
+//! - transparent materials (`d` < 1, `Tr` > 0, or `illum` 4, 6, 7 or 9) become [`Dielectric`] with
+//!   the `Ni` refraction index,
+//! - metallic materials (`Pm` > 0.5, or `illum` 3 or 5) become [`Metal`] with the `Kd` albedo and a
+//!   fuzz derived from the `Ns` specular exponent, 1000 being a perfect mirror,
+//! - everything else becomes [`Lambertian`] with the `Kd` albedo.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::{FromStr, SplitWhitespace};
+use std::sync::Arc;
+use std::{fs, io};
+
+use derive_more::Display;
+
+use crate::material::{Dielectric, DiffuseLight, Lambertian, Material, Metal};
+use crate::object::Hittable;
+use crate::triangle::{MeshFace, MeshTriangle, TriangleMesh};
+use crate::types::{Color, Point2, Point3, Vector3};
+
+const DEFAULT_ALBEDO: Color = Color::new(0.8, 0.8, 0.8);
+const DEFAULT_REFRACTION_INDEX: f64 = 1.5;
+const MAX_SPECULAR_EXPONENT: f64 = 1000.0;
+
+#[derive(Debug, Display)]
+pub enum ObjError {
+    #[display("{}: {source}", path.display())]
+    Io { path: PathBuf, source: io::Error },
+
+    #[display("{}:{line}: {message}", path.display())]
+    Parse {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+}
+
+impl std::error::Error for ObjError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ObjError::Io { source, .. } => Some(source),
+            ObjError::Parse { .. } => None,
+        }
+    }
+}
+
+/// Part of a model sharing a group name and a material.
+pub struct ObjGroup {
+    pub name: String,
+    pub material_name: Option<String>,
+    pub mesh: TriangleMesh<Arc<dyn Material>>,
+}
+
+pub struct ObjModel {
+    pub groups: Vec<ObjGroup>,
+}
+
+impl ObjModel {
+    /// Load an OBJ file, along with the MTL files it references relatively to its directory.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ObjError> {
+        let path = path.as_ref();
+        let mut parser = ObjParser::new();
+
+        for (index, line) in read(path)?.lines().enumerate() {
+            let mut tokens = line.split_whitespace();
+            if tokens.next() == Some("mtllib") {
+                for file in tokens {
+                    parser
+                        .materials
+                        .extend(load_mtl(&path.with_file_name(file))?);
+                }
+            } else {
+                parser
+                    .parse_line(line)
+                    .map_err(|message| parse_error(path, index + 1, message))?;
+            }
+        }
+
+        Ok(parser.finish())
+    }
+
+    /// Number of triangles over all groups.
+    pub fn len(&self) -> usize {
+        self.groups.iter().map(|group| group.mesh.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.iter().all(|group| group.mesh.is_empty())
+    }
+
+    /// Split the model into one object per triangle, e.g. to build a
+    /// [`Bvh`](crate::bvh::Bvh).
+    pub fn into_triangles(self) -> Vec<Box<dyn Hittable>> {
+        self.groups
+            .into_iter()
+            .flat_map(|group| group.mesh.into_triangles())
+            .collect()
+    }
+
+    /// Split the model into the triangles of all groups, which keep access to their material.
+    pub fn into_mesh_triangles(self) -> Vec<MeshTriangle<Arc<dyn Material>>> {
+        self.groups
+            .into_iter()
+            .flat_map(|group| group.mesh.into_mesh_triangles())
+            .collect()
+    }
+}
+
+/// Faces of a group using a single material, indexing the buffers of the whole file.
+struct Section {
+    name: String,
+    material_name: Option<String>,
+    faces: Vec<MeshFace>,
+}
+
+struct ObjParser {
+    positions: Vec<Point3>,
+    normals: Vec<Vector3>,
+    uvs: Vec<Point2>,
+    materials: HashMap<String, Arc<dyn Material>>,
+    default_material: Arc<dyn Material>,
+    sections: Vec<Section>,
+    group: String,
+    material_name: Option<String>,
+}
+
+impl ObjParser {
+    fn new() -> Self {
+        Self {
+            positions: Vec::new(),
+            normals: Vec::new(),
+            uvs: Vec::new(),
+            materials: HashMap::new(),
+            default_material: Arc::new(Lambertian::new(DEFAULT_ALBEDO)),
+            sections: Vec::new(),
+            group: String::from("default"),
+            material_name: None,
+        }
+    }
+
+    fn parse_line(&mut self, line: &str) -> Result<(), String> {
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            return Ok(());
+        };
+
+        match keyword {
+            "v" => {
+                let position = parse_vector3(&mut tokens, "vertex position")?;
+                self.positions.push(position);
+            },
+            "vn" => {
+                let normal = parse_vector3(&mut tokens, "vertex normal")?;
+                self.normals.push(normal);
+            },
+            "vt" => {
+                let u = parse_number(tokens.next(), "texture coordinate")?;
+                let v = tokens
+                    .next()
+                    .map_or(Ok(0.0), |v| parse_number(Some(v), "texture coordinate"))?;
+                self.uvs.push(Point2::new(u, v));
+            },
+            "f" => self.parse_face(tokens)?,
+            "g" | "o" => {
+                let name = tokens.collect::<Vec<_>>().join(" ");
+                self.group = if name.is_empty() {
+                    String::from("default")
+                } else {
+                    name
+                };
+            },
+            "usemtl" => {
+                let name = tokens.next().ok_or("missing material name")?;
+                if !self.materials.contains_key(name) {
+                    return Err(format!("undefined material `{name}`"));
+                }
+                self.material_name = Some(name.to_string());
+            },
+            // Comments, smoothing groups, lines, points and unsupported statements.
+            _ => {},
+        }
+
+        Ok(())
+    }
+
+    /// Parse a polygon and add it as a fan of triangles around its first vertex.
+    fn parse_face(&mut self, tokens: SplitWhitespace) -> Result<(), String> {
+        let vertices = tokens
+            .map(|token| self.parse_face_vertex(token))
+            .collect::<Result<Vec<_>, _>>()?;
+        if vertices.len() < 3 {
+            return Err(format!(
+                "face has {} vertices, expected at least 3",
+                vertices.len()
+            ));
+        }
+
+        let section = self.section();
+        for k in 1..vertices.len() - 1 {
+            let corners = [vertices[0], vertices[k], vertices[k + 1]];
+            let indices = |index: fn(&FaceVertex) -> Option<usize>| match corners
+                .map(|vertex| index(&vertex))
+            {
+                [Some(a), Some(b), Some(c)] => Some([a, b, c]),
+                _ => None,
+            };
+
+            section.faces.push(MeshFace {
+                positions: corners.map(|vertex| vertex.position),
+                normals: indices(|vertex| vertex.normal),
+                uvs: indices(|vertex| vertex.uv),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Parse a face vertex of the form `v`, `v/vt`, `v//vn` or `v/vt/vn`.
+    fn parse_face_vertex(&self, token: &str) -> Result<FaceVertex, String> {
+        let mut indices = token.split('/');
+        let position = resolve_index(indices.next(), self.positions.len(), "vertex")?
+            .ok_or_else(|| format!("missing vertex index in `{token}`"))?;
+        let uv = resolve_index(indices.next(), self.uvs.len(), "texture coordinate")?;
+        let normal = resolve_index(indices.next(), self.normals.len(), "normal")?;
+
+        Ok(FaceVertex {
+            position,
+            uv,
+            normal,
+        })
+    }
+
+    /// Section for the current group and material, started anew when either changes.
+    fn section(&mut self) -> &mut Section {
+        let current = self.sections.last().is_some_and(|section| {
+            section.name == self.group && section.material_name == self.material_name
+        });
+        if !current {
+            self.sections.push(Section {
+                name: self.group.clone(),
+                material_name: self.material_name.clone(),
+                faces: Vec::new(),
+            });
+        }
+        self.sections.last_mut().unwrap()
+    }
+
+    fn finish(self) -> ObjModel {
+        let groups = self
+            .sections
+            .into_iter()
+            .filter(|section| !section.faces.is_empty())
+            .map(|section| {
+                let material = match &section.material_name {
+                    Some(name) => Arc::clone(&self.materials[name]),
+                    None => Arc::clone(&self.default_material),
+                };
+
+                // Only keep the part of the file buffers used by the section.
+                let mut positions = Compactor::new(&self.positions);
+                let mut normals = Compactor::new(&self.normals);
+                let mut uvs = Compactor::new(&self.uvs);
+                let faces = section
+                    .faces
+                    .iter()
+                    .map(|face| MeshFace {
+                        positions: positions.remap(face.positions),
+                        normals: face.normals.map(|indices| normals.remap(indices)),
+                        uvs: face.uvs.map(|indices| uvs.remap(indices)),
+                    })
+                    .collect();
+
+                ObjGroup {
+                    name: section.name,
+                    material_name: section.material_name,
+                    mesh: TriangleMesh::new(
+                        positions.values,
+                        normals.values,
+                        uvs.values,
+                        faces,
+                        material,
+                    ),
+                }
+            })
+            .collect();
+
+        ObjModel { groups }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+struct FaceVertex {
+    position: usize,
+    uv: Option<usize>,
+    normal: Option<usize>,
+}
+
+/// Copy the values of a buffer on first use, mapping their indices to the copy.
+struct Compactor<'a, T> {
+    source: &'a [T],
+    indices: HashMap<usize, usize>,
+    values: Vec<T>,
+}
+
+impl<'a, T: Copy> Compactor<'a, T> {
+    fn new(source: &'a [T]) -> Self {
+        Self {
+            source,
+            indices: HashMap::new(),
+            values: Vec::new(),
+        }
+    }
+
+    fn remap(&mut self, indices: [usize; 3]) -> [usize; 3] {
+        indices.map(|index| {
+            *self.indices.entry(index).or_insert_with(|| {
+                self.values.push(self.source[index]);
+                self.values.len() - 1
+            })
+        })
+    }
+}
+
+/// Parse an MTL file into materials by name.
+fn load_mtl(path: &Path) -> Result<HashMap<String, Arc<dyn Material>>, ObjError> {
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, MtlMaterial)> = None;
+
+    for (index, line) in read(path)?.lines().enumerate() {
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        let error = |message: String| parse_error(path, index + 1, message);
+
+        if keyword == "newmtl" {
+            let name = tokens
+                .next()
+                .ok_or_else(|| error("missing material name".into()))?;
+            if let Some((name, material)) = current.take() {
+                materials.insert(name, material.build());
+            }
+            current = Some((name.to_string(), MtlMaterial::default()));
+            continue;
+        }
+
+        let Some((_, material)) = current.as_mut() else {
+            if keyword.starts_with('#') {
+                continue;
+            }
+            return Err(error(format!("`{keyword}` before any `newmtl`")));
+        };
+
+        let mut number = |what| parse_number(tokens.next(), what).map_err(error);
+        match keyword {
+            "Kd" => {
+                let diffuse = parse_vector3(&mut tokens, "diffuse color").map_err(error)?;
+                material.diffuse = Some(Color(diffuse));
+            },
+            "Ke" => {
+                let emission = parse_vector3(&mut tokens, "emissive color").map_err(error)?;
+                material.emission = Some(Color(emission));
+            },
+            "Ns" => material.specular_exponent = Some(number("specular exponent")?),
+            "Ni" => material.refraction_index = Some(number("refraction index")?),
+            "d" => material.dissolve = Some(number("dissolve")?),
+            "Tr" => material.dissolve = Some(1.0 - number("transparency")?),
+            "Pm" => material.metallic = Some(number("metallic")?),
+            "illum" => material.illumination = Some(number("illumination model")? as u32),
+            // Comments and unsupported statements.
+            _ => {},
+        }
+    }
+
+    if let Some((name, material)) = current {
+        materials.insert(name, material.build());
+    }
+
+    Ok(materials)
+}
+
+#[derive(Debug, Default)]
+struct MtlMaterial {
+    diffuse: Option<Color>,
+    emission: Option<Color>,
+    specular_exponent: Option<f64>,
+    refraction_index: Option<f64>,
+    dissolve: Option<f64>,
+    metallic: Option<f64>,
+    illumination: Option<u32>,
+}
+
+impl MtlMaterial {
+    fn build(self) -> Arc<dyn Material> {
+        let albedo = self.diffuse.unwrap_or(DEFAULT_ALBEDO);
+        let transparent = self.dissolve.is_some_and(|dissolve| dissolve < 1.0)
+            || matches!(self.illumination, Some(4 | 6 | 7 | 9));
+        let metallic = self.metallic.is_some_and(|metallic| metallic > 0.5)
+            || matches!(self.illumination, Some(3 | 5));
+
+        if let Some(emission) = self.emission.filter(|emission| *emission != Color::BLACK) {
+            Arc::new(DiffuseLight::new(emission))
+        } else if transparent {
+            let refraction_index = self.refraction_index.unwrap_or(DEFAULT_REFRACTION_INDEX);
+            Arc::new(Dielectric::new(refraction_index))
+        } else if metallic {
+            let shininess = self.specular_exponent.unwrap_or(MAX_SPECULAR_EXPONENT);
+            let fuzz = 1.0 - (shininess / MAX_SPECULAR_EXPONENT).clamp(0.0, 1.0).sqrt();
+            Arc::new(Metal::new(albedo, fuzz))
+        } else {
+            Arc::new(Lambertian::new(albedo))
+        }
+    }
+}
+
+fn read(path: &Path) -> Result<String, ObjError> {
+    fs::read_to_string(path).map_err(|source| ObjError::Io {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+fn parse_error(path: &Path, line: usize, message: String) -> ObjError {
+    ObjError::Parse {
+        path: path.to_path_buf(),
+        line,
+        message,
+    }
+}
+
+fn parse_number<T: FromStr>(token: Option<&str>, what: &str) -> Result<T, String> {
+    let token = token.ok_or_else(|| format!("missing {what}"))?;
+    token
+        .parse()
+        .map_err(|_| format!("invalid {what} `{token}`"))
+}
+
+fn parse_vector3<'a>(
+    tokens: &mut impl Iterator<Item = &'a str>,
+    what: &str,
+) -> Result<Vector3, String> {
+    Ok(Vector3::new(
+        parse_number(tokens.next(), what)?,
+        parse_number(tokens.next(), what)?,
+        parse_number(tokens.next(), what)?,
+    ))
+}
+
+/// Resolve a 1-based, or negative and relative to the end, OBJ index into a buffer of `len`
+/// elements. An empty or missing token means the index is absent.
+fn resolve_index(token: Option<&str>, len: usize, what: &str) -> Result<Option<usize>, String> {
+    let Some(token) = token.filter(|token| !token.is_empty()) else {
+        return Ok(None);
+    };
+
+    let index: i64 = parse_number(Some(token), &format!("{what} index"))?;
+    let resolved = if index < 0 {
+        len as i64 + index
+    } else {
+        index - 1
+    };
+
+    if (0..len as i64).contains(&resolved) {
+        Ok(Some(resolved as usize))
+    } else {
+        Err(format!(
+            "{what} index {index} out of range, {len} defined so far"
+        ))
+    }
+}
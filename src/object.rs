@@ -1,7 +1,8 @@
 use std::ops::{Deref, Range};
 
+use crate::aabb::Aabb;
 use crate::material::Material;
-use crate::types::{Point3, Ray, Vector3};
+use crate::types::{Point2, Point3, Ray, Vector3};
 
 #[derive(Copy, Clone, Debug)]
 pub struct Hit<'a> {
@@ -10,6 +11,12 @@ pub struct Hit<'a> {
     pub normal: Vector3,
     pub front_face: bool,
     pub material: &'a dyn Material,
+
+    // Surface coordinates of the hit point
+    pub uv: Point2,
+
+    // Barycentric coordinates of the hit point, for hits on triangles
+    pub barycentric: Option<Vector3>,
 }
 
 impl<'a> Hit<'a> {
@@ -34,28 +41,60 @@ impl<'a> Hit<'a> {
             normal,
             front_face,
             material,
+            uv: Point2::zeros(),
+            barycentric: None,
         }
     }
+
+    pub fn with_uv(mut self, uv: Point2) -> Self {
+        self.uv = uv;
+        self
+    }
+
+    pub fn with_barycentric(mut self, barycentric: Vector3) -> Self {
+        self.barycentric = Some(barycentric);
+        self
+    }
+
+    /// Replace the normal used for shading, keeping it on the same side of the surface as the
+    /// geometric one.
+    ///
+    /// NOTE: the parameter `shading_normal` is assumed to have unit length.
+    pub fn with_shading_normal(mut self, shading_normal: Vector3) -> Self {
+        self.normal = if shading_normal.dot(&self.normal) < 0.0 {
+            -shading_normal
+        } else {
+            shading_normal
+        };
+        self
+    }
 }
 
-pub trait Hittable {
-    fn hit(&self, ray: &Ray, t_range: Range<f64>) -> Option<Hit>;
+pub trait Hittable: Send + Sync {
+    fn hit(&self, ray: &Ray, t_range: Range<f64>) -> Option<Hit<'_>>;
+
+    /// Axis-aligned box enclosing the whole object.
+    fn bounding_box(&self) -> Aabb;
 }
 
 impl<T> Hittable for T
 where
-    T: Deref<Target = dyn Hittable>,
+    T: Deref<Target = dyn Hittable> + Send + Sync,
 {
-    fn hit(&self, ray: &Ray, t_range: Range<f64>) -> Option<Hit> {
+    fn hit(&self, ray: &Ray, t_range: Range<f64>) -> Option<Hit<'_>> {
         self.deref().hit(ray, t_range)
     }
+
+    fn bounding_box(&self) -> Aabb {
+        self.deref().bounding_box()
+    }
 }
 
 impl<T> Hittable for [T]
 where
     T: Hittable,
 {
-    fn hit(&self, ray: &Ray, t_range: Range<f64>) -> Option<Hit> {
+    fn hit(&self, ray: &Ray, t_range: Range<f64>) -> Option<Hit<'_>> {
         let mut nearest_hit = None;
         let mut closest_so_far = t_range.end;
 
@@ -68,4 +107,45 @@ where
 
         nearest_hit
     }
+
+    fn bounding_box(&self) -> Aabb {
+        self.iter().fold(Aabb::EMPTY, |bbox, hittable| {
+            bbox.union(&hittable.bounding_box())
+        })
+    }
+}
+
+/// Objects grouped into a single one, tested one after the other. Only suited to a few objects,
+/// a [`Bvh`](crate::bvh::Bvh) being faster for many.
+pub struct HittableList<T> {
+    objects: Vec<T>,
+    bbox: Aabb,
+}
+
+impl<T: Hittable> HittableList<T> {
+    pub fn new(objects: Vec<T>) -> Self {
+        let bbox = objects.as_slice().bounding_box();
+        Self { objects, bbox }
+    }
+
+    pub fn objects(&self) -> &[T] {
+        &self.objects
+    }
+
+    pub fn into_objects(self) -> Vec<T> {
+        self.objects
+    }
+}
+
+impl<T: Hittable> Hittable for HittableList<T> {
+    fn hit(&self, ray: &Ray, t_range: Range<f64>) -> Option<Hit<'_>> {
+        if !self.bbox.hit(ray, t_range.clone()) {
+            return None;
+        }
+        self.objects.as_slice().hit(ray, t_range)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
 }
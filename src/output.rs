@@ -0,0 +1,280 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Cursor, Write};
+use std::path::{Path, PathBuf};
+
+use derive_more::{Display, From};
+use exr::prelude::{
+    f16, AnyChannel, AnyChannels, Encoding, FlatSamples, Image, Layer, LayerAttributes,
+    WritableImage,
+};
+use image::codecs::hdr::HdrEncoder;
+use image::codecs::png::PngEncoder;
+use image::{ExtendedColorType, ImageEncoder, ImageError, Rgb};
+
+use crate::frame::Frame;
+use crate::tonemap::DisplayTransform;
+use crate::types::Color;
+
+#[derive(Debug, Display, From)]
+pub enum OutputError {
+    #[display("{_0}")]
+    Io(io::Error),
+
+    #[display("{_0}")]
+    Image(ImageError),
+
+    #[display("{_0}")]
+    Exr(exr::error::Error),
+
+    #[display("unsupported output format for `{}`", _0.display())]
+    #[from(ignore)]
+    UnsupportedFormat(PathBuf),
+}
+
+impl std::error::Error for OutputError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            OutputError::Io(source) => Some(source),
+            OutputError::Image(source) => Some(source),
+            OutputError::Exr(source) => Some(source),
+            OutputError::UnsupportedFormat(_) => None,
+        }
+    }
+}
+
+/// Encoder of frames of linear radiance.
+///
+/// Low dynamic range formats convert the colors with a [`DisplayTransform`], high dynamic range
+/// ones store the radiance as is.
+pub trait ImageWriter {
+    fn write(&self, frame: &Frame, out: &mut dyn Write) -> Result<(), OutputError>;
+
+    fn write_to_path(&self, frame: &Frame, path: &Path) -> Result<(), OutputError> {
+        let mut out = BufWriter::new(File::create(path)?);
+        self.write(frame, &mut out)?;
+        out.flush()?;
+        Ok(())
+    }
+}
+
+/// Select a writer from the extension of `path`: `.ppm` for binary PPM, `.png` for 8-bit PNG,
+/// `.exr` for half float OpenEXR, `.hdr` for Radiance RGBE and `.pfm` for PFM. Low dynamic range
+/// writers convert the colors with `display`.
+pub fn writer_for_path(
+    path: &Path,
+    display: &DisplayTransform,
+) -> Result<Box<dyn ImageWriter>, OutputError> {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+
+    match extension.as_deref() {
+        Some("ppm") => Ok(Box::new(PpmWriter::binary().with_display(display.clone()))),
+        Some("png") => Ok(Box::new(
+            PngWriter::new(BitDepth::Eight).with_display(display.clone()),
+        )),
+        Some("exr") => Ok(Box::new(ExrWriter::new(Precision::Half))),
+        Some("hdr") => Ok(Box::new(HdrWriter)),
+        Some("pfm") => Ok(Box::new(PfmWriter)),
+        _ => Err(OutputError::UnsupportedFormat(path.to_path_buf())),
+    }
+}
+
+/// Pixels of `frame` row by row, along with their column and row.
+fn indexed_pixels(frame: &Frame) -> impl Iterator<Item = (usize, usize, Color)> + '_ {
+    frame
+        .pixels()
+        .iter()
+        .enumerate()
+        .map(|(index, color)| (index % frame.width(), index / frame.width(), *color))
+}
+
+/// Netpbm color image, either as ASCII (P3) or binary (P6).
+#[derive(Clone, Debug)]
+pub struct PpmWriter {
+    binary: bool,
+    display: DisplayTransform,
+}
+
+impl PpmWriter {
+    pub fn ascii() -> Self {
+        Self {
+            binary: false,
+            display: DisplayTransform::default(),
+        }
+    }
+
+    pub fn binary() -> Self {
+        Self {
+            binary: true,
+            display: DisplayTransform::default(),
+        }
+    }
+
+    pub fn with_display(mut self, display: DisplayTransform) -> Self {
+        self.display = display;
+        self
+    }
+}
+
+impl ImageWriter for PpmWriter {
+    fn write(&self, frame: &Frame, out: &mut dyn Write) -> Result<(), OutputError> {
+        let mut out = BufWriter::new(out);
+        let magic = if self.binary { "P6" } else { "P3" };
+        writeln!(out, "{magic}\n{} {}\n255", frame.width(), frame.height())?;
+
+        for (i, j, color) in indexed_pixels(frame) {
+            let [r, g, b] = self.display.to_u8(color, i, j);
+            if self.binary {
+                out.write_all(&[r, g, b])?;
+            } else {
+                writeln!(out, "{r} {g} {b}")?;
+            }
+        }
+
+        out.flush()?;
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BitDepth {
+    Eight,
+    Sixteen,
+}
+
+#[derive(Clone, Debug)]
+pub struct PngWriter {
+    bit_depth: BitDepth,
+    display: DisplayTransform,
+}
+
+impl PngWriter {
+    pub fn new(bit_depth: BitDepth) -> Self {
+        Self {
+            bit_depth,
+            display: DisplayTransform::default(),
+        }
+    }
+
+    pub fn with_display(mut self, display: DisplayTransform) -> Self {
+        self.display = display;
+        self
+    }
+}
+
+impl ImageWriter for PngWriter {
+    fn write(&self, frame: &Frame, out: &mut dyn Write) -> Result<(), OutputError> {
+        let (width, height) = (frame.width() as u32, frame.height() as u32);
+        let encoder = PngEncoder::new(out);
+
+        match self.bit_depth {
+            BitDepth::Eight => {
+                let bytes: Vec<u8> = indexed_pixels(frame)
+                    .flat_map(|(i, j, color)| self.display.to_u8(color, i, j))
+                    .collect();
+                encoder.write_image(&bytes, width, height, ExtendedColorType::Rgb8)?;
+            },
+            BitDepth::Sixteen => {
+                // The encoder takes 16-bit samples as native endian bytes.
+                let bytes: Vec<u8> = indexed_pixels(frame)
+                    .flat_map(|(i, j, color)| self.display.to_u16(color, i, j))
+                    .flat_map(u16::to_ne_bytes)
+                    .collect();
+                encoder.write_image(&bytes, width, height, ExtendedColorType::Rgb16)?;
+            },
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Precision {
+    Half,
+    Float,
+}
+
+/// OpenEXR image with R, G and B channels.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ExrWriter {
+    precision: Precision,
+}
+
+impl ExrWriter {
+    pub fn new(precision: Precision) -> Self {
+        Self { precision }
+    }
+}
+
+impl ImageWriter for ExrWriter {
+    fn write(&self, frame: &Frame, out: &mut dyn Write) -> Result<(), OutputError> {
+        let samples = |component: fn(&Color) -> f64| {
+            let values = frame.pixels().iter().map(component);
+            match self.precision {
+                Precision::Half => FlatSamples::F16(values.map(f16::from_f64).collect()),
+                Precision::Float => FlatSamples::F32(values.map(|value| value as f32).collect()),
+            }
+        };
+
+        let channels = vec![
+            AnyChannel::new("R", samples(|color| color.x)),
+            AnyChannel::new("G", samples(|color| color.y)),
+            AnyChannel::new("B", samples(|color| color.z)),
+        ];
+
+        let layer = Layer::new(
+            (frame.width(), frame.height()),
+            LayerAttributes::default(),
+            Encoding::FAST_LOSSLESS,
+            AnyChannels::sort(channels.into()),
+        );
+
+        // The encoder needs to seek, which the output stream may not support.
+        let mut buffer = Cursor::new(Vec::new());
+        Image::from_layer(layer).write().to_buffered(&mut buffer)?;
+        out.write_all(buffer.get_ref())?;
+        Ok(())
+    }
+}
+
+/// Radiance RGBE image, run length encoded.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct HdrWriter;
+
+impl ImageWriter for HdrWriter {
+    fn write(&self, frame: &Frame, out: &mut dyn Write) -> Result<(), OutputError> {
+        let pixels: Vec<_> = frame
+            .pixels()
+            .iter()
+            .map(|color| Rgb(color.to_f32().map(|c| c.max(0.0))))
+            .collect();
+        HdrEncoder::new(out).encode(&pixels, frame.width(), frame.height())?;
+        Ok(())
+    }
+}
+
+/// Portable float map, with little endian 32-bit float samples.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PfmWriter;
+
+impl ImageWriter for PfmWriter {
+    fn write(&self, frame: &Frame, out: &mut dyn Write) -> Result<(), OutputError> {
+        let mut out = BufWriter::new(out);
+        // A negative scale stands for little endian data.
+        writeln!(out, "PF\n{} {}\n-1.0", frame.width(), frame.height())?;
+
+        // Rows go from the bottom to the top of the image.
+        for row in frame.pixels().chunks(frame.width().max(1)).rev() {
+            for color in row {
+                for sample in color.to_f32() {
+                    out.write_all(&sample.to_le_bytes())?;
+                }
+            }
+        }
+
+        out.flush()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,336 @@
+//! Flat primitives lying in the plane spanned by two edges from a corner, which only differ by
+//! the region of the plane they cover.
+
+use std::f64::consts::PI;
+use std::fmt;
+use std::ops::Range;
+
+use crate::aabb::Aabb;
+use crate::light::{Light, LightSample};
+use crate::material::Material;
+use crate::object::{Hit, Hittable, HittableList};
+use crate::types::{concentric_disk_sample, Basis, Point2, Point3, Ray, Vector3};
+
+// Thickness given to the bounding box of shapes lying in an axis-aligned plane.
+const BBOX_PADDING: f64 = 1e-4;
+
+/// Region of a plane, described in the plane coordinates `(alpha, beta)` of the point
+/// `q + alpha * u + beta * v`.
+pub trait Shape: fmt::Debug + Send + Sync {
+    /// Surface coordinates of the point at `(alpha, beta)`, or `None` if it is outside the shape.
+    fn contains(&self, alpha: f64, beta: f64) -> Option<Point2>;
+
+    /// Area of the shape in plane coordinates, that is relative to the area of the parallelogram
+    /// spanned by `u` and `v`.
+    fn area(&self) -> f64;
+
+    /// Plane coordinates of a point sampled uniformly over the shape, using the 2D sample `u`.
+    fn sample(&self, u: Point2) -> Point2;
+
+    /// Lower and upper corners of the rectangle of plane coordinates enclosing the shape.
+    fn bounds(&self) -> (Point2, Point2);
+}
+
+/// Parallelogram spanned by the edges from the corner, with surface coordinates going from 0 to 1
+/// along each edge.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Parallelogram;
+
+impl Shape for Parallelogram {
+    fn contains(&self, alpha: f64, beta: f64) -> Option<Point2> {
+        ((0.0..=1.0).contains(&alpha) && (0.0..=1.0).contains(&beta))
+            .then(|| Point2::new(alpha, beta))
+    }
+
+    fn area(&self) -> f64 {
+        1.0
+    }
+
+    fn sample(&self, u: Point2) -> Point2 {
+        u
+    }
+
+    fn bounds(&self) -> (Point2, Point2) {
+        (Point2::zeros(), Point2::new(1.0, 1.0))
+    }
+}
+
+/// Triangle whose vertices are the corner and the ends of both edges, with the barycentric
+/// coordinates of the ends of the edges as surface coordinates.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TriangleShape;
+
+impl Shape for TriangleShape {
+    fn contains(&self, alpha: f64, beta: f64) -> Option<Point2> {
+        (alpha >= 0.0 && beta >= 0.0 && alpha + beta <= 1.0).then(|| Point2::new(alpha, beta))
+    }
+
+    fn area(&self) -> f64 {
+        0.5
+    }
+
+    fn sample(&self, u: Point2) -> Point2 {
+        let sqrt = u.x.sqrt();
+        Point2::new(sqrt * (1.0 - u.y), sqrt * u.y)
+    }
+
+    fn bounds(&self) -> (Point2, Point2) {
+        (Point2::zeros(), Point2::new(1.0, 1.0))
+    }
+}
+
+/// Ellipse centered on the corner, with the edges as semi-axes. The surface coordinates map the
+/// enclosing parallelogram to 0..1 on each axis.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Ellipse;
+
+impl Shape for Ellipse {
+    fn contains(&self, alpha: f64, beta: f64) -> Option<Point2> {
+        (alpha.powi(2) + beta.powi(2) <= 1.0)
+            .then(|| Point2::new(0.5 * (alpha + 1.0), 0.5 * (beta + 1.0)))
+    }
+
+    fn area(&self) -> f64 {
+        PI
+    }
+
+    fn sample(&self, u: Point2) -> Point2 {
+        concentric_disk_sample(u)
+    }
+
+    fn bounds(&self) -> (Point2, Point2) {
+        (Point2::new(-1.0, -1.0), Point2::new(1.0, 1.0))
+    }
+}
+
+/// Ellipse centered on the corner, with the edges as semi-axes, and a hole of `inner` times its
+/// size. The surface coordinates are the angle around the center from the first edge, and the
+/// distance from the inner to the outer border, both normalized to 0..1.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Annulus {
+    inner: f64,
+}
+
+impl Annulus {
+    pub fn new(inner: f64) -> Self {
+        Self {
+            inner: inner.clamp(0.0, 1.0 - f64::EPSILON),
+        }
+    }
+}
+
+impl Shape for Annulus {
+    fn contains(&self, alpha: f64, beta: f64) -> Option<Point2> {
+        let radius = (alpha.powi(2) + beta.powi(2)).sqrt();
+        if !(self.inner..=1.0).contains(&radius) {
+            return None;
+        }
+
+        let phi = beta.atan2(alpha).rem_euclid(2.0 * PI);
+        Some(Point2::new(
+            phi / (2.0 * PI),
+            (radius - self.inner) / (1.0 - self.inner),
+        ))
+    }
+
+    fn area(&self) -> f64 {
+        PI * (1.0 - self.inner.powi(2))
+    }
+
+    fn sample(&self, u: Point2) -> Point2 {
+        let inner_squared = self.inner.powi(2);
+        let radius = (inner_squared + u.x * (1.0 - inner_squared)).sqrt();
+        let phi = 2.0 * PI * u.y;
+        Point2::new(radius * phi.cos(), radius * phi.sin())
+    }
+
+    fn bounds(&self) -> (Point2, Point2) {
+        (Point2::new(-1.0, -1.0), Point2::new(1.0, 1.0))
+    }
+}
+
+/// Shape lying in the plane spanned by the edges `u` and `v` from the corner `q`.
+#[derive(Clone)]
+pub struct Planar<S, M> {
+    shape: S,
+    q: Point3,
+    u: Vector3,
+    v: Vector3,
+    material: M,
+
+    // Unit normal, and offset of the plane along it
+    normal: Vector3,
+    d: f64,
+
+    // Normal over its squared length, giving the plane coordinates of points of the plane
+    w: Vector3,
+
+    area: f64,
+}
+
+/// Parallelogram spanned by the edges `u` and `v` from the corner `q`.
+pub type Quad<M> = Planar<Parallelogram, M>;
+
+impl<S: Shape, M: Material> Planar<S, M> {
+    /// NOTE: the edges `u` and `v` are assumed to be nonzero and not parallel.
+    pub fn from_shape(shape: S, q: Point3, u: Vector3, v: Vector3, material: M) -> Self {
+        let n = u.cross(&v);
+        let normal = n.normalize();
+        let area = n.norm() * shape.area();
+
+        Self {
+            shape,
+            q,
+            u,
+            v,
+            material,
+            normal,
+            d: normal.dot(&q),
+            w: n / n.norm_squared(),
+            area,
+        }
+    }
+
+    pub fn material(&self) -> &M {
+        &self.material
+    }
+
+    /// Density with respect to solid angle of sampling the point of `hit`, reached along the
+    /// unit `direction`, uniformly over the area of the shape.
+    fn solid_angle_pdf(&self, direction: &Vector3, hit: &Hit) -> f64 {
+        let cosine = direction.dot(&self.normal).abs();
+        if cosine <= 0.0 || self.area <= 0.0 {
+            return 0.0;
+        }
+
+        // The ray parameter is the distance, the direction being a unit vector.
+        hit.t.powi(2) / (cosine * self.area)
+    }
+}
+
+impl<M: Material> Planar<Parallelogram, M> {
+    pub fn new(q: Point3, u: Vector3, v: Vector3, material: M) -> Self {
+        Self::from_shape(Parallelogram, q, u, v, material)
+    }
+}
+
+impl<M: Material> Planar<TriangleShape, M> {
+    /// Triangle of vertices `a`, `b` and `c`, facing the side they turn counterclockwise around.
+    pub fn triangle(a: Point3, b: Point3, c: Point3, material: M) -> Self {
+        Self::from_shape(TriangleShape, a, b - a, c - a, material)
+    }
+}
+
+impl<M: Material> Planar<Ellipse, M> {
+    /// Ellipse of semi-axes `u` and `v` around `center`.
+    pub fn ellipse(center: Point3, u: Vector3, v: Vector3, material: M) -> Self {
+        Self::from_shape(Ellipse, center, u, v, material)
+    }
+
+    /// Disk of `radius` around `center`, facing the direction of `normal`.
+    ///
+    /// NOTE: the parameter `normal` is assumed to be nonzero.
+    pub fn disk(center: Point3, normal: Vector3, radius: f64, material: M) -> Self {
+        let basis = Basis::from_w(normal.normalize());
+        Self::ellipse(center, radius * basis.u, radius * basis.v, material)
+    }
+}
+
+impl<M: Material> Planar<Annulus, M> {
+    /// Ring between `inner_radius` and `radius` around `center`, facing the direction of
+    /// `normal`.
+    ///
+    /// NOTE: the parameter `normal` is assumed to be nonzero.
+    pub fn annulus(
+        center: Point3,
+        normal: Vector3,
+        radius: f64,
+        inner_radius: f64,
+        material: M,
+    ) -> Self {
+        let basis = Basis::from_w(normal.normalize());
+        let shape = Annulus::new(inner_radius / radius);
+        Self::from_shape(shape, center, radius * basis.u, radius * basis.v, material)
+    }
+}
+
+impl<S: Shape, M: Material> Hittable for Planar<S, M> {
+    fn hit(&self, ray: &Ray, t_range: Range<f64>) -> Option<Hit<'_>> {
+        let denominator = self.normal.dot(&ray.direction);
+
+        // The ray is parallel to the plane.
+        if denominator.abs() < 1e-8 {
+            return None;
+        }
+
+        let t = (self.d - self.normal.dot(&ray.origin)) / denominator;
+        if !t_range.contains(&t) {
+            return None;
+        }
+
+        // Plane coordinates of the hit point
+        let point = ray.at(t);
+        let planar = point - self.q;
+        let alpha = self.w.dot(&planar.cross(&self.v));
+        let beta = self.w.dot(&self.u.cross(&planar));
+        let uv = self.shape.contains(alpha, beta)?;
+
+        Some(Hit::new(t, point, ray, self.normal, &self.material).with_uv(uv))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let (min, max) = self.shape.bounds();
+        let at = |alpha: f64, beta: f64| self.q + alpha * self.u + beta * self.v;
+
+        Aabb::new(at(min.x, min.y), at(max.x, max.y))
+            .union(&Aabb::new(at(max.x, min.y), at(min.x, max.y)))
+            .padded(BBOX_PADDING)
+    }
+}
+
+impl<S: Shape, M: Material> Light for Planar<S, M> {
+    /// Sample a point uniformly over the area of the shape.
+    fn sample(&self, origin: &Point3, time: f64, u: Point2) -> Option<LightSample<'_>> {
+        let coordinates = self.shape.sample(u);
+        let point = self.q + coordinates.x * self.u + coordinates.y * self.v;
+        let direction = (point - origin).normalize();
+        let hit = self.hit(
+            &Ray::new(*origin, direction).with_time(time),
+            0.0..f64::INFINITY,
+        )?;
+
+        Some(LightSample {
+            hit,
+            direction,
+            pdf: self.solid_angle_pdf(&direction, &hit),
+        })
+    }
+
+    fn pdf(&self, origin: &Point3, time: f64, direction: &Vector3) -> f64 {
+        self.hit(
+            &Ray::new(*origin, *direction).with_time(time),
+            0.0..f64::INFINITY,
+        )
+        .map_or(0.0, |hit| self.solid_angle_pdf(direction, &hit))
+    }
+}
+
+/// The six sides of the axis-aligned box spanned by the opposite corners `a` and `b`.
+pub fn make_box<M: Material + Clone>(a: Point3, b: Point3, material: M) -> HittableList<Quad<M>> {
+    let min = a.inf(&b);
+    let max = a.sup(&b);
+
+    let dx = Vector3::new(max.x - min.x, 0.0, 0.0);
+    let dy = Vector3::new(0.0, max.y - min.y, 0.0);
+    let dz = Vector3::new(0.0, 0.0, max.z - min.z);
+
+    HittableList::new(vec![
+        // Front, right, back, left, top and bottom
+        Quad::new(Point3::new(min.x, min.y, max.z), dx, dy, material.clone()),
+        Quad::new(Point3::new(max.x, min.y, max.z), -dz, dy, material.clone()),
+        Quad::new(Point3::new(max.x, min.y, min.z), -dx, dy, material.clone()),
+        Quad::new(Point3::new(min.x, min.y, min.z), dz, dy, material.clone()),
+        Quad::new(Point3::new(min.x, max.y, max.z), dx, -dz, material.clone()),
+        Quad::new(Point3::new(min.x, min.y, min.z), dx, dz, material),
+    ])
+}
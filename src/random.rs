@@ -0,0 +1,28 @@
+use rand::SeedableRng;
+
+/// Random number generator used by every sampling routine of the renderer.
+///
+/// It is seeded explicitly, so that a given seed always produces the same image.
+pub type Rng = rand_pcg::Pcg64Mcg;
+
+pub fn seeded(seed: u64) -> Rng {
+    Rng::seed_from_u64(seed)
+}
+
+/// Hash of a sequence of values, from which independent seeds or random values can be derived.
+pub fn hash(values: &[u64]) -> u64 {
+    values.iter().fold(0, |hash, &value| mix(hash ^ mix(value)))
+}
+
+/// Uniform value in 0..1 made of the high bits of `bits`.
+pub fn unit_f64(bits: u64) -> f64 {
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// SplitMix64 finalizer, which turns nearby inputs into unrelated outputs.
+fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
@@ -1,15 +1,44 @@
+use std::mem;
+use std::ops::Range;
+
+use rayon::prelude::*;
+use rayon::ThreadPoolBuildError;
+
 use crate::camera::Camera;
-use crate::object::Hittable;
-use crate::types::{Color, Point3, Ray, Vector3};
+use crate::checkpoint::{Checkpoint, CheckpointError};
+use crate::filter::{BoxFilter, Filter, FilterSampler};
+use crate::frame::{Accumulator, Frame, PixelStats};
+use crate::light::{self, Light};
+use crate::object::{Hit, Hittable};
+use crate::sampler::{Dimension, Independent, SampleKey, Sampler};
+use crate::types::{Color, Ray, Vector3};
 use crate::utils::Timer;
 
+// Distances along rays at which hits are considered, the start keeping rays leaving a surface
+// from hitting it again because of rounding errors.
+const HIT_RANGE: Range<f64> = 0.001..f64::INFINITY;
+
 pub struct Renderer {
     image_width: usize,
     image_height: usize,
     camera: Camera,
-    frame: Vec<Color>,
+    accumulator: Accumulator,
     samples_per_pixel: usize,
+    samples_per_pass: usize,
+    sampler: Box<dyn Sampler>,
+    filter: Box<dyn Filter>,
+    filter_sampler: FilterSampler,
+    adaptive_sampling: Option<AdaptiveSampling>,
     max_depth: usize,
+    roulette_depth: usize,
+    mis_heuristic: MisHeuristic,
+    lights: Vec<Box<dyn Light>>,
+
+    // Worker threads, the global thread pool being used when there are none
+    pool: Option<rayon::ThreadPool>,
+
+    seed: u64,
+    background: Background,
 }
 
 impl Renderer {
@@ -18,160 +47,467 @@ impl Renderer {
             image_width,
             image_height,
             camera,
-            frame: Vec::with_capacity(image_width * image_height),
+            accumulator: Accumulator::new(image_width, image_height),
             samples_per_pixel: 5,
+            samples_per_pass: 4,
+            sampler: Box::new(Independent),
+            filter: Box::new(BoxFilter::default()),
+            filter_sampler: FilterSampler::new(&BoxFilter::default()),
+            adaptive_sampling: None,
             max_depth: 10,
+            roulette_depth: 3,
+            mis_heuristic: MisHeuristic::Power,
+            lights: Vec::new(),
+            pool: None,
+            seed: 0,
+            background: Background::Sky,
         }
     }
 
+    /// Change the image resolution, adjusting the camera viewport to it.
+    pub fn with_image_size(mut self, image_width: usize, image_height: usize) -> Self {
+        self.camera = mem::take(&mut self.camera).with_viewport_size(image_width, image_height);
+        self.image_width = image_width;
+        self.image_height = image_height;
+        self.accumulator = Accumulator::new(image_width, image_height);
+        self
+    }
+
     pub fn with_samples_per_pixel(mut self, samples_per_pixel: usize) -> Self {
         self.samples_per_pixel = samples_per_pixel;
         self
     }
 
+    /// Set the source of the sample values of the pixel, lens, BSDF and light dimensions.
+    pub fn with_sampler(mut self, sampler: impl Sampler + 'static) -> Self {
+        self.sampler = Box::new(sampler);
+        self
+    }
+
+    /// Set the reconstruction filter the samples of each pixel are spread and weighted by.
+    pub fn with_filter(mut self, filter: impl Filter + 'static) -> Self {
+        self.filter_sampler = FilterSampler::new(&filter);
+        self.filter = Box::new(filter);
+        self
+    }
+
+    /// Set how many samples each pixel gets at most in a pass.
+    pub fn with_samples_per_pass(mut self, samples_per_pass: usize) -> Self {
+        self.samples_per_pass = samples_per_pass.max(1);
+        self
+    }
+
+    /// Stop sampling pixels whose estimate has converged, `samples_per_pixel` becoming the
+    /// maximum number of samples of a pixel.
+    pub fn with_adaptive_sampling(mut self, adaptive_sampling: AdaptiveSampling) -> Self {
+        self.adaptive_sampling = Some(adaptive_sampling);
+        self
+    }
+
+    /// Set the number of bounces after which paths are cut off, whatever light they may still
+    /// carry.
     pub fn with_max_depth(mut self, max_depth: usize) -> Self {
         self.max_depth = max_depth;
         self
     }
 
+    /// Set the number of bounces after which paths are terminated at random with Russian
+    /// roulette, with a probability growing as their throughput drops. Surviving paths are
+    /// scaled up to make up for the terminated ones, so that the image stays unbiased.
+    pub fn with_roulette_depth(mut self, roulette_depth: usize) -> Self {
+        self.roulette_depth = roulette_depth;
+        self
+    }
+
+    /// Set the emissive objects sampled explicitly at every bounce. Other emissive objects are
+    /// only found by following the directions sampled by materials.
+    pub fn with_lights(mut self, lights: Vec<Box<dyn Light>>) -> Self {
+        self.lights = lights;
+        self
+    }
+
+    /// Set how light sampling and BSDF sampling are weighted against each other.
+    pub fn with_mis_heuristic(mut self, mis_heuristic: MisHeuristic) -> Self {
+        self.mis_heuristic = mis_heuristic;
+        self
+    }
+
+    /// Set what rays escaping the scene see, and thus the light coming from outside of it.
+    pub fn with_background(mut self, background: Background) -> Self {
+        self.background = background;
+        self
+    }
+
+    /// Set the number of worker threads, 0 meaning one per available core. The threads are
+    /// started once here and reused by every pass.
+    pub fn with_threads(mut self, threads: usize) -> Result<Self, ThreadPoolBuildError> {
+        self.pool = if threads > 0 {
+            Some(
+                rayon::ThreadPoolBuilder::new()
+                    .num_threads(threads)
+                    .build()?,
+            )
+        } else {
+            None
+        };
+        Ok(self)
+    }
+
+    /// Set the seed from which the samples of every pixel are derived.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn image_width(&self) -> usize {
+        self.image_width
+    }
+
+    pub fn image_height(&self) -> usize {
+        self.image_height
+    }
+
+    pub fn samples_per_pixel(&self) -> usize {
+        self.samples_per_pixel
+    }
+
+    pub fn sampler(&self) -> &dyn Sampler {
+        self.sampler.as_ref()
+    }
+
+    pub fn filter(&self) -> &dyn Filter {
+        self.filter.as_ref()
+    }
+
+    pub fn roulette_depth(&self) -> usize {
+        self.roulette_depth
+    }
+
+    pub fn mis_heuristic(&self) -> MisHeuristic {
+        self.mis_heuristic
+    }
+
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Render every pixel until it is complete, discarding any previous samples.
     pub fn render<const FADING_N: usize, T: Hittable + ?Sized>(
         &mut self,
         world: &T,
         fading: Fading<FADING_N>,
     ) -> Timer {
-        if !self.frame.is_empty() {
-            self.frame = Vec::with_capacity(self.image_width * self.image_height);
-        }
-        let pixel_samples_scale = 1.0 / self.samples_per_pixel as f64;
-
         let mut timer = Timer::start();
 
-        for j in 0..self.image_height {
-            for i in 0..self.image_width {
-                let mut pixel_color = Color::BLACK;
-                let fading = match fading {
-                    Fading::Const(fading) => fading,
-                    Fading::Ramp(fadings) => fadings[i * fadings.len() / self.image_width],
-                };
-
-                if self.samples_per_pixel == 1 {
-                    pixel_color += self.ray_color(self.get_ray(i, j), world, fading);
-                } else if self.samples_per_pixel == 5 {
-                    for ray in self.get_rays(i, j) {
-                        pixel_color += self.ray_color(ray, world, fading);
-                    }
-                } else {
-                    for _ in 0..self.samples_per_pixel {
-                        let ray = self.get_random_ray(i, j);
-                        pixel_color += self.ray_color(ray, world, fading);
-                    }
-                }
-
-                self.frame.push(
-                    (pixel_samples_scale * pixel_color)
-                        .to_gamma_2_color()
-                        .clamp(),
-                );
-            }
+        self.accumulator = Accumulator::new(self.image_width, self.image_height);
+        while !self.is_complete() {
+            self.render_pass(world, fading);
         }
 
         timer.stop();
         timer
     }
 
-    pub fn output(&self) -> Timer {
+    /// Whether every pixel has `samples_per_pixel` samples, or has converged with adaptive
+    /// sampling.
+    pub fn is_complete(&self) -> bool {
+        self.accumulator
+            .pixels()
+            .iter()
+            .all(|pixel| self.pass_samples(pixel) == 0)
+    }
+
+    /// Add up to `samples_per_pass` samples to every pixel which is not complete yet, rendering
+    /// rows of pixels in parallel rather than tiles.
+    ///
+    /// Each sample is derived from the seed, the pixel coordinates and its index within the
+    /// pixel, so that rendering in several passes gives the same result as in a single one.
+    pub fn render_pass<const FADING_N: usize, T: Hittable + ?Sized>(
+        &mut self,
+        world: &T,
+        fading: Fading<FADING_N>,
+    ) -> Timer {
         let mut timer = Timer::start();
 
-        println!("P3\n{} {}\n255", self.image_width, self.image_height);
-        for j in 0..self.image_height {
-            for i in 0..self.image_width {
-                let color = self.frame[i + j * self.image_width].to_byte();
-                println!("{} {} {}", color[0], color[1], color[2]);
-            }
+        // Rows are the unit of work: being contiguous in the accumulator, each is a disjoint
+        // slice rendered in place without copying, and images have far more rows than there are
+        // threads, so that work stealing keeps them all busy. The accumulator is moved out
+        // meanwhile so that the renderer settings can still be shared by the threads.
+        let mut accumulator = mem::replace(&mut self.accumulator, Accumulator::new(0, 0));
+        let mut render = || {
+            accumulator
+                .pixels_mut()
+                .par_chunks_mut(self.image_width)
+                .enumerate()
+                .for_each(|(j, row)| self.render_row(j, row, world, &fading));
+        };
+        match &self.pool {
+            Some(pool) => pool.install(render),
+            None => render(),
         }
+        self.accumulator = accumulator;
 
         timer.stop();
         timer
     }
 
-    /// Construct a camera ray originating from the origin and directed at randomly sampled
-    /// point around the pixel location i, j.
-    pub fn get_random_ray(&self, i: usize, j: usize) -> Ray {
-        let origin = self.camera.origin();
-        let direction = self.camera.pixel_center(i, j, Some(sample_square())) - origin;
-
-        Ray::new(origin, direction)
-    }
-
-    pub fn get_ray(&self, i: usize, j: usize) -> Ray {
-        let origin = self.camera.origin();
-        Ray::new(origin, self.camera.pixel_center(i, j, None) - origin)
-    }
-
-    pub fn get_rays(&self, i: usize, j: usize) -> [Ray; 5] {
-        let origin = self.camera.origin();
-
-        [
-            Ray::new(
-                origin,
-                self.camera
-                    .pixel_center(i, j, Some(Point3::new(0.5, 0.5, 0.0)))
-                    - origin,
-            ),
-            Ray::new(
-                origin,
-                self.camera
-                    .pixel_center(i, j, Some(Point3::new(-0.5, 0.5, 0.0)))
-                    - origin,
-            ),
-            Ray::new(
-                origin,
-                self.camera
-                    .pixel_center(i, j, Some(Point3::new(0.5, -0.5, 0.0)))
-                    - origin,
-            ),
-            Ray::new(
-                origin,
-                self.camera
-                    .pixel_center(i, j, Some(Point3::new(-0.5, -0.5, 0.0)))
-                    - origin,
-            ),
-            self.get_ray(i, j),
-        ]
-    }
-
-    fn ray_color<T: Hittable + ?Sized>(&self, ray: Ray, world: &T, fading: f64) -> Color {
-        self.ray_color_diffuse_random(ray, world, fading)
-    }
-
-    fn ray_color_diffuse_random<T: Hittable + ?Sized>(
+    /// Number of samples to add to a pixel in the next pass.
+    fn pass_samples(&self, pixel: &PixelStats) -> usize {
+        let samples = pixel.samples as usize;
+        let remaining = self.samples_per_pixel.saturating_sub(samples);
+
+        match self.adaptive_sampling {
+            Some(adaptive) if samples < adaptive.min_samples => (adaptive.min_samples - samples)
+                .max(self.samples_per_pass)
+                .min(remaining),
+            Some(adaptive) if pixel.relative_error() <= adaptive.threshold => 0,
+            _ => remaining.min(self.samples_per_pass),
+        }
+    }
+
+    /// Render the next pass of the pixels of row `j`, updating their statistics.
+    fn render_row<const FADING_N: usize, T: Hittable + ?Sized>(
         &self,
+        j: usize,
+        row: &mut [PixelStats],
+        world: &T,
+        fading: &Fading<FADING_N>,
+    ) {
+        for (i, pixel) in row.iter_mut().enumerate() {
+            let samples = self.pass_samples(pixel);
+            self.render_pixel(i, j, pixel, samples, world, fading);
+        }
+    }
+
+    /// Add `samples` radiance samples to the statistics of pixel i, j.
+    fn render_pixel<const FADING_N: usize, T: Hittable + ?Sized>(
+        &self,
+        i: usize,
+        j: usize,
+        pixel: &mut PixelStats,
+        samples: usize,
+        world: &T,
+        fading: &Fading<FADING_N>,
+    ) {
+        let fading = match fading {
+            Fading::Const(fading) => *fading,
+            Fading::Ramp(fadings) => fadings[i * fadings.len() / self.image_width],
+        };
+
+        for _ in 0..samples {
+            let key = SampleKey {
+                seed: self.seed,
+                i,
+                j,
+                index: pixel.samples as usize,
+            };
+            let (ray, weight) = self.get_ray(&key);
+            pixel.add(self.ray_color(&key, ray, world, fading), weight);
+        }
+    }
+
+    /// Mean radiance of the samples rendered so far.
+    pub fn frame(&self) -> Frame {
+        self.accumulator.frame()
+    }
+
+    pub fn accumulator(&self) -> &Accumulator {
+        &self.accumulator
+    }
+
+    /// Snapshot of the samples rendered so far, `scene_hash` identifying what they depict.
+    pub fn checkpoint(&self, scene_hash: u64) -> Checkpoint {
+        Checkpoint {
+            seed: self.seed,
+            scene_hash,
+            accumulator: self.accumulator.clone(),
+        }
+    }
+
+    /// Continue from the samples of `checkpoint`, provided that it was rendered from the same
+    /// scene and at the same image size.
+    pub fn resume(
+        &mut self,
+        checkpoint: Checkpoint,
+        scene_hash: u64,
+    ) -> Result<(), CheckpointError> {
+        let accumulator = checkpoint.accumulator;
+        if (accumulator.width(), accumulator.height()) != (self.image_width, self.image_height) {
+            return Err(CheckpointError::SizeMismatch {
+                width: self.image_width,
+                height: self.image_height,
+                found_width: accumulator.width(),
+                found_height: accumulator.height(),
+            });
+        }
+        if checkpoint.scene_hash != scene_hash {
+            return Err(CheckpointError::SceneMismatch);
+        }
+
+        self.seed = checkpoint.seed;
+        self.accumulator = accumulator;
+        Ok(())
+    }
+
+    /// Camera ray of a sample, through the point around pixel i, j drawn from the filter and from
+    /// the point of the lens given by the sampler, at the time of the shutter interval given by
+    /// the sampler, along with the filter weight of the sample.
+    pub fn get_ray(&self, key: &SampleKey) -> (Ray, f64) {
+        let origin = self
+            .camera
+            .origin(self.sampler.get_2d(key, Dimension::Lens));
+        let (offset, weight) = self
+            .filter_sampler
+            .sample(self.sampler.get_2d(key, Dimension::Pixel));
+        let target =
+            self.camera
+                .pixel_center(key.i, key.j, Some(Vector3::new(offset.x, offset.y, 0.0)));
+
+        let time = self.camera.time(self.sampler.get_1d(key, Dimension::Time));
+
+        (Ray::new(origin, target - origin).with_time(time), weight)
+    }
+
+    fn ray_color<T: Hittable + ?Sized>(
+        &self,
+        key: &SampleKey,
+        ray: Ray,
+        world: &T,
+        fading: f64,
+    ) -> Color {
+        self.ray_color_path(key, ray, world, fading)
+    }
+
+    /// Path tracing, gathering at every bounce the light of a point sampled on the lights and
+    /// the light emitted where the sampled BSDF direction leads, the two being combined with
+    /// multiple importance sampling.
+    fn ray_color_path<T: Hittable + ?Sized>(
+        &self,
+        key: &SampleKey,
         mut ray: Ray,
         world: &T,
         fading: f64,
     ) -> Color {
+        let mut radiance = Color::BLACK;
         let mut attenuation = Color::WHITE;
         let mut acc_fading = 1.0;
         let mut bounds = 0;
 
+        // Density of the BSDF direction the ray was sampled from, none for camera rays and delta
+        // lobes, which light sampling cannot produce
+        let mut bsdf_pdf = None;
+
         loop {
             if bounds > self.max_depth {
-                break Color::BLACK;
-            } else if let Some(hit) = world.hit(&ray, 0.001..f64::INFINITY) {
-                if let Some((scattered, new_attenuation)) = hit.material.scatter(&ray, &hit) {
-                    ray = scattered;
-                    attenuation *= new_attenuation;
+                break radiance;
+            } else if let Some(hit) = world.hit(&ray, HIT_RANGE) {
+                let emitted = hit.material.emitted(&hit);
+                if emitted != Color::BLACK {
+                    let weight = bsdf_pdf.map_or(1.0, |bsdf_pdf| {
+                        self.mis_heuristic
+                            .weight(bsdf_pdf, self.light_pdf(&ray, &hit))
+                    });
+                    radiance += attenuation * (acc_fading * weight * emitted);
+                }
+
+                let wo = -ray.direction;
+                if bounds < self.max_depth {
+                    let direct = self.sample_lights(key, bounds, ray.time, &wo, &hit, world);
+                    radiance += attenuation * (acc_fading * fading * direct);
+                }
+
+                let u = self.sampler.get_2d(key, Dimension::Bsdf(bounds));
+                if let Some(sample) = hit.material.sample(&hit, &wo, u) {
+                    ray = Ray::new(hit.point, sample.direction).with_time(ray.time);
+                    attenuation *= sample.weight;
                     acc_fading *= fading;
                     bounds += 1;
+                    bsdf_pdf = (!sample.delta).then_some(sample.pdf);
                 } else {
-                    break Color::BLACK;
+                    break radiance;
+                }
+
+                if bounds > self.roulette_depth {
+                    let survival = attenuation.max().min(1.0);
+                    if self.sampler.get_1d(key, Dimension::Roulette(bounds)) >= survival {
+                        break radiance;
+                    }
+                    attenuation = (1.0 / survival) * attenuation;
                 }
             } else {
-                break attenuation * (acc_fading * Color::gradient_white_to_blue(ray.direction.y));
+                break radiance + attenuation * (acc_fading * self.background.color(&ray));
             }
         }
     }
 
+    /// Light reaching the hit point at `time` directly from a point sampled on one of the lights,
+    /// picked at random, and scattered along `wo`, weighted for multiple importance sampling.
+    fn sample_lights<T: Hittable + ?Sized>(
+        &self,
+        key: &SampleKey,
+        bounce: usize,
+        time: f64,
+        wo: &Vector3,
+        hit: &Hit,
+        world: &T,
+    ) -> Color {
+        if self.lights.is_empty() {
+            return Color::BLACK;
+        }
+
+        let u = self.sampler.get_2d(key, Dimension::Light(bounce));
+        let (index, u) = light::pick_light(self.lights.len(), u);
+        let Some(sample) = self.lights[index].sample(&hit.point, time, u) else {
+            return Color::BLACK;
+        };
+
+        let wi = &sample.direction;
+        let f = hit.material.f(hit, wo, wi);
+        if f == Color::BLACK {
+            return Color::BLACK;
+        }
+
+        // Shadow ray, stopping short of the light itself
+        let shadow = Ray::new(hit.point, *wi).with_time(time);
+        if world
+            .hit(&shadow, HIT_RANGE.start..sample.hit.t - HIT_RANGE.start)
+            .is_some()
+        {
+            return Color::BLACK;
+        }
+
+        let light_pdf = sample.pdf / self.lights.len() as f64;
+        let weight = self
+            .mis_heuristic
+            .weight(light_pdf, hit.material.pdf(hit, wo, wi));
+        let cosine = wi.dot(&hit.normal).abs();
+
+        (weight * cosine / light_pdf) * f * sample.hit.material.emitted(&sample.hit)
+    }
+
+    /// Density with respect to solid angle of light sampling choosing the direction of `ray`,
+    /// from its origin to `hit`. Emissive objects which are not among the lights cannot be
+    /// chosen.
+    fn light_pdf(&self, ray: &Ray, hit: &Hit) -> f64 {
+        let pdf: f64 = self
+            .lights
+            .iter()
+            .filter(|light| {
+                light
+                    .hit(ray, HIT_RANGE)
+                    .is_some_and(|light_hit| (light_hit.t - hit.t).abs() <= 1e-9 * hit.t.max(1.0))
+            })
+            .map(|light| light.pdf(&ray.origin, ray.time, &ray.direction))
+            .sum();
+
+        pdf / self.lights.len().max(1) as f64
+    }
+
+    #[allow(dead_code)]
     fn ray_color_normal<T: Hittable + ?Sized>(&self, ray: Ray, world: &T) -> Color {
         // Objects normal color
         if let Some(hit) = world.hit(&ray, 0.0..f64::INFINITY) {
@@ -183,15 +519,63 @@ impl Renderer {
     }
 }
 
-// Returns the vector to a random point in the [-.5,-.5] - [+.5,+.5] unit square.
-fn sample_square() -> Vector3 {
-    Vector3::new(
-        rand::random::<f64>() - 0.5,
-        rand::random::<f64>() - 0.5,
-        0.0,
-    )
+/// Adaptive sampling settings: a pixel is complete once it has at least `min_samples` samples
+/// and the standard error of its mean luminance relative to the mean is below `threshold`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AdaptiveSampling {
+    pub min_samples: usize,
+    pub threshold: f64,
+}
+
+/// Multiple importance sampling heuristic, weighting a sample of one strategy according to its
+/// density and the density of the other strategy producing it.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MisHeuristic {
+    /// Weights proportional to the densities.
+    Balance,
+
+    /// Weights proportional to the squared densities, favoring the best strategy more.
+    Power,
+}
+
+impl MisHeuristic {
+    /// Weight of a sample drawn with density `pdf` when the other strategy has density
+    /// `other_pdf` for it.
+    pub fn weight(self, pdf: f64, other_pdf: f64) -> f64 {
+        let (pdf, other_pdf) = match self {
+            MisHeuristic::Balance => (pdf, other_pdf),
+            MisHeuristic::Power => (pdf * pdf, other_pdf * other_pdf),
+        };
+
+        if pdf.is_infinite() {
+            1.0
+        } else if pdf + other_pdf > 0.0 {
+            pdf / (pdf + other_pdf)
+        } else {
+            0.0
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Background {
+    /// White to blue vertical gradient.
+    Sky,
+
+    /// Uniform color, black making the scene only lit by its emissive materials.
+    Solid(Color),
+}
+
+impl Background {
+    pub fn color(&self, ray: &Ray) -> Color {
+        match self {
+            Background::Sky => Color::gradient_white_to_blue(ray.direction.y),
+            Background::Solid(color) => *color,
+        }
+    }
 }
 
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Fading<const N: usize = 2> {
     Const(f64),
     Ramp([f64; N]),
@@ -0,0 +1,285 @@
+//! Sample values driving the random decisions along camera paths.
+//!
+//! Every sample of every pixel is made of independent dimensions, such as the position within
+//! the pixel or the point on the lens. A [`Sampler`] provides the values of each dimension
+//! directly from the sample identity, so that samples do not depend on the rendering order and
+//! low discrepancy sequences can spread the values of a pixel evenly.
+
+use std::fmt;
+
+use crate::random;
+use crate::types::Point2;
+
+/// What a sample value is used for, each dimension getting its own values.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Dimension {
+    /// Position within the pixel.
+    Pixel,
+
+    /// Point on the camera lens.
+    Lens,
+
+    /// Instant within the shutter interval of the camera.
+    Time,
+
+    /// Scattering direction at the given bounce.
+    Bsdf(usize),
+
+    /// Point on a light source at the given bounce.
+    Light(usize),
+
+    /// Russian roulette decision after the given bounce.
+    Roulette(usize),
+}
+
+impl Dimension {
+    /// Position of the dimension in the sequence of 2D dimensions of a sample.
+    pub fn index(self) -> usize {
+        match self {
+            Dimension::Pixel => 0,
+            Dimension::Lens => 1,
+            Dimension::Time => 2,
+            Dimension::Bsdf(bounce) => 3 + 3 * bounce,
+            Dimension::Light(bounce) => 4 + 3 * bounce,
+            Dimension::Roulette(bounce) => 5 + 3 * bounce,
+        }
+    }
+}
+
+/// Identity of a sample: the render seed, the pixel coordinates and the index of the sample
+/// within the pixel.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SampleKey {
+    pub seed: u64,
+    pub i: usize,
+    pub j: usize,
+    pub index: usize,
+}
+
+impl SampleKey {
+    /// Hash of the pixel and dimension, the same for every sample of the pixel.
+    fn pixel_hash(&self, dimension: Dimension) -> u64 {
+        random::hash(&[
+            self.seed,
+            self.i as u64,
+            self.j as u64,
+            dimension.index() as u64,
+        ])
+    }
+
+    /// Hash of the sample and dimension.
+    fn sample_hash(&self, dimension: Dimension) -> u64 {
+        random::hash(&[self.pixel_hash(dimension), self.index as u64])
+    }
+}
+
+pub trait Sampler: fmt::Debug + Send + Sync {
+    /// Value in 0..1 of a 1D dimension of the sample.
+    fn get_1d(&self, key: &SampleKey, dimension: Dimension) -> f64 {
+        self.get_2d(key, dimension).x
+    }
+
+    /// Value in 0..1 × 0..1 of a 2D dimension of the sample.
+    fn get_2d(&self, key: &SampleKey, dimension: Dimension) -> Point2;
+}
+
+/// Uniform random values, independent from one sample to the next.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Independent;
+
+impl Sampler for Independent {
+    fn get_2d(&self, key: &SampleKey, dimension: Dimension) -> Point2 {
+        unit_square_point(key.sample_hash(dimension))
+    }
+}
+
+/// Uniform random point in 0..1 × 0..1 derived from `hash`.
+fn unit_square_point(hash: u64) -> Point2 {
+    Point2::new(
+        random::unit_f64(hash),
+        random::unit_f64(random::hash(&[hash])),
+    )
+}
+
+/// Jittered values, one in each stratum of a grid of about `samples_per_pixel` cells, in an
+/// order shuffled per pixel and dimension. Samples past `samples_per_pixel` are independent.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Stratified {
+    samples_per_pixel: usize,
+    columns: usize,
+    rows: usize,
+}
+
+impl Stratified {
+    pub fn new(samples_per_pixel: usize) -> Self {
+        let samples_per_pixel = samples_per_pixel.max(1);
+        let columns = (samples_per_pixel as f64).sqrt().ceil() as usize;
+        let rows = samples_per_pixel.div_ceil(columns);
+
+        Self {
+            samples_per_pixel,
+            columns,
+            rows,
+        }
+    }
+}
+
+impl Sampler for Stratified {
+    fn get_1d(&self, key: &SampleKey, dimension: Dimension) -> f64 {
+        if key.index >= self.samples_per_pixel {
+            return Independent.get_1d(key, dimension);
+        }
+
+        let count = self.samples_per_pixel as u32;
+        let stratum = permute(key.index as u32, count, key.pixel_hash(dimension) as u32);
+        let jitter = random::unit_f64(key.sample_hash(dimension));
+        (stratum as f64 + jitter) / count as f64
+    }
+
+    fn get_2d(&self, key: &SampleKey, dimension: Dimension) -> Point2 {
+        if key.index >= self.samples_per_pixel {
+            return Independent.get_2d(key, dimension);
+        }
+
+        let cells = (self.columns * self.rows) as u32;
+        let stratum = permute(key.index as u32, cells, key.pixel_hash(dimension) as u32) as usize;
+        let jitter = Independent.get_2d(key, dimension);
+        Point2::new(
+            ((stratum % self.columns) as f64 + jitter.x) / self.columns as f64,
+            ((stratum / self.columns) as f64 + jitter.y) / self.rows as f64,
+        )
+    }
+}
+
+/// Kensler's hashed permutation of `0..len`, returning the position of `index` for the
+/// permutation selected by `pattern`.
+fn permute(mut index: u32, len: u32, pattern: u32) -> u32 {
+    let mut mask = len - 1;
+    mask |= mask >> 1;
+    mask |= mask >> 2;
+    mask |= mask >> 4;
+    mask |= mask >> 8;
+    mask |= mask >> 16;
+
+    // Permute within the next power of two, until the result falls within the range.
+    loop {
+        index ^= pattern;
+        index = index.wrapping_mul(0xe170893d);
+        index ^= pattern >> 16;
+        index ^= (index & mask) >> 4;
+        index ^= pattern >> 8;
+        index = index.wrapping_mul(0x0929eb3f);
+        index ^= pattern >> 23;
+        index ^= (index & mask) >> 1;
+        index = index.wrapping_mul(1 | pattern >> 27);
+        index = index.wrapping_mul(0x6935fa69);
+        index ^= (index & mask) >> 11;
+        index = index.wrapping_mul(0x74dcb303);
+        index ^= (index & mask) >> 2;
+        index = index.wrapping_mul(0x9e501cc3);
+        index ^= (index & mask) >> 2;
+        index = index.wrapping_mul(0xc860a3df);
+        index &= mask;
+        index ^= index >> 5;
+        if index < len {
+            break;
+        }
+    }
+
+    (index + pattern) % len
+}
+
+// Bases of the Halton sequence, two per 2D dimension.
+const PRIMES: [u64; 64] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131, 137, 139, 149, 151, 157, 163, 167, 173, 179, 181, 191, 193,
+    197, 199, 211, 223, 227, 229, 233, 239, 241, 251, 257, 263, 269, 271, 277, 281, 283, 293, 307,
+    311,
+];
+
+/// Halton sequence, decorrelated between pixels by a random toroidal shift per pixel and
+/// dimension. Dimensions past the first 32 are independent.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Halton;
+
+impl Sampler for Halton {
+    fn get_2d(&self, key: &SampleKey, dimension: Dimension) -> Point2 {
+        let base = 2 * dimension.index();
+        if base + 1 >= PRIMES.len() {
+            return Independent.get_2d(key, dimension);
+        }
+
+        let shift = unit_square_point(key.pixel_hash(dimension));
+        let index = key.index as u64;
+        Point2::new(
+            (radical_inverse(index, PRIMES[base]) + shift.x).fract(),
+            (radical_inverse(index, PRIMES[base + 1]) + shift.y).fract(),
+        )
+    }
+}
+
+/// Mirror the digits of `index` in `base` around the radix point.
+fn radical_inverse(mut index: u64, base: u64) -> f64 {
+    let inv_base = 1.0 / base as f64;
+    let mut inv_base_power = 1.0;
+    let mut reversed = 0;
+
+    while index > 0 {
+        reversed = reversed * base + index % base;
+        inv_base_power *= inv_base;
+        index /= base;
+    }
+
+    (reversed as f64 * inv_base_power).min(1.0 - f64::EPSILON)
+}
+
+/// First two dimensions of the Sobol sequence, Owen scrambled and with their order shuffled
+/// independently for every pixel and dimension, following Burley's "Practical Hash-based Owen
+/// Scrambling".
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Sobol;
+
+impl Sampler for Sobol {
+    fn get_2d(&self, key: &SampleKey, dimension: Dimension) -> Point2 {
+        let seed = key.pixel_hash(dimension);
+        let [shuffle_seed, x_seed, y_seed] = [0, 1, 2].map(|n| random::hash(&[seed, n]) as u32);
+
+        let index = nested_uniform_scramble(key.index as u32, shuffle_seed);
+        let x = nested_uniform_scramble(index.reverse_bits(), x_seed);
+        let y = nested_uniform_scramble(sobol_second_dimension(index), y_seed);
+
+        const SCALE: f64 = 1.0 / (1u64 << 32) as f64;
+        Point2::new(x as f64 * SCALE, y as f64 * SCALE)
+    }
+}
+
+/// Second dimension of the Sobol sequence, as a 32-bit binary fraction.
+fn sobol_second_dimension(index: u32) -> u32 {
+    let mut direction = 1 << 31;
+    let mut value = 0;
+
+    for bit in 0..32 {
+        if index & (1 << bit) != 0 {
+            value ^= direction;
+        }
+        direction ^= direction >> 1;
+    }
+
+    value
+}
+
+/// Owen scrambling of a 32-bit binary fraction: each digit is flipped depending on the digits
+/// above it.
+fn nested_uniform_scramble(value: u32, seed: u32) -> u32 {
+    laine_karras_permutation(value.reverse_bits(), seed).reverse_bits()
+}
+
+/// Hash in which each bit only depends on the bits below it.
+fn laine_karras_permutation(mut x: u32, seed: u32) -> u32 {
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50b47c);
+    x ^= x.wrapping_mul(0xb82f1e52);
+    x ^= x.wrapping_mul(0xc7afe638);
+    x ^= x.wrapping_mul(0x8d22f6e6);
+    x
+}
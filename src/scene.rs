@@ -0,0 +1,792 @@
+//! Declarative scene description files.
+//!
+//! A scene file is a TOML document with the following tables, all optional:
+//!
+//! ```toml
+//! [camera]
+//! lookfrom = [13.0, 2.0, 3.0]
+//! lookat = [0.0, 0.0, 0.0]
+//! viewup = [0.0, 1.0, 0.0]
+//! vertical_fov = 20.0
+//! focus_dist = 10.0
+//! defocus_angle = 0.6
+//! shutter = [0.0, 1.0]  # times the shutter opens and closes at, for motion blur
+//!
+//! [renderer]
+//! width = 1200
+//! aspect_ratio = 1.7778 # or `height = 675`
+//! samples_per_pixel = 100
+//! max_depth = 50
+//! roulette_depth = 3
+//! fading = 0.98         # or a ramp `[0.9, 1.0]` across the image
+//! background = "sky"    # or a color `[0.0, 0.0, 0.0]`
+//! seed = 0
+//!
+//! [materials.ground]
+//! type = "lambertian"   # or "metal", "dielectric", "diffuse_light"
+//! albedo = { type = "checker", scale = 0.32, even = [0.2, 0.3, 0.1], odd = [0.9, 0.9, 0.9] }
+//!
+//! [[objects]]
+//! type = "sphere"       # or "triangle", "quad", "disk", "ellipse", "annulus", "box", "obj"
+//! center = [0.0, -1000.0, 0.0]
+//! radius = 1000.0
+//! material = "ground"
+//! ```
+//!
+//! Spheres may move, with a `center` given as keyframes `[{ time = 0.0, position = [0.0, 1.0,
+//! 0.0] }, ...]` interpolated linearly over time.
+//!
+//! Quads are given by a corner `q` and two edges `u` and `v`, ellipses by a `center` and two
+//! semi-axes `u` and `v`, disks by a `center`, a `normal` and a `radius`, annuli like disks with an
+//! additional `inner_radius`, and boxes by two opposite corners `a` and `b`.
+//!
+//! OBJ models take an optional `scale` (a factor or one per axis), `rotate` (`{ axis = [0.0, 1.0,
+//! 0.0], angle = 30.0 }` in degrees) and `translate`, applied in that order. Models are instances
+//! sharing a single copy of the geometry of each file, so that a model can be placed many times
+//! at little cost.
+//!
+//! Every primitive made of a `diffuse_light` material, boxes and OBJ models (through their `Ke`
+//! color) included, is also sampled as a light.
+//!
+//! Textures are either a plain color or a table of type `solid`, `checker`, `image` or `noise`.
+//! Relative paths are resolved from the directory of the scene file.
+
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::{fs, io};
+
+use derive_more::Display;
+use nalgebra::Affine3;
+use serde::Deserialize;
+
+use crate::bvh::Bvh;
+use crate::camera::Camera;
+use crate::instance::Instance;
+use crate::light::Light;
+use crate::material::{Dielectric, DiffuseLight, Lambertian, Material, Metal};
+use crate::motion::Motion;
+use crate::obj::{ObjError, ObjModel};
+use crate::object::Hittable;
+use crate::planar::{make_box, Planar, Quad};
+use crate::random::{self, Rng};
+use crate::render::{Background, Fading, Renderer};
+use crate::sphere::Sphere;
+use crate::texture::{Checker, ImageTexture, NoiseMode, NoiseTexture, SolidColor, Texture};
+use crate::triangle::{MeshTriangle, Triangle};
+use crate::types::{Color, Point3, Vector3};
+
+const DEFAULT_IMAGE_WIDTH: usize = 400;
+const DEFAULT_ASPECT_RATIO: f64 = 16.0 / 9.0;
+
+#[derive(Debug, Display)]
+pub enum SceneError {
+    #[display("{}: {source}", path.display())]
+    Io { path: PathBuf, source: io::Error },
+
+    #[display("{}: {source}", path.display())]
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+
+    #[display("{}: invalid `{key}`: {message}", path.display())]
+    Invalid {
+        path: PathBuf,
+        key: String,
+        message: String,
+    },
+
+    #[display("{_0}")]
+    Obj(ObjError),
+}
+
+impl std::error::Error for SceneError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SceneError::Io { source, .. } => Some(source),
+            SceneError::Parse { source, .. } => Some(source),
+            SceneError::Invalid { .. } => None,
+            SceneError::Obj(source) => Some(source),
+        }
+    }
+}
+
+/// Everything needed to render an image: a configured renderer and the objects to render.
+pub struct Scene {
+    pub renderer: Renderer,
+    pub world: Vec<Box<dyn Hittable>>,
+    pub fading: Fading,
+}
+
+impl Scene {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SceneError> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path).map_err(|source| SceneError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let description: SceneDescription =
+            toml::from_str(&source).map_err(|source| SceneError::Parse {
+                path: path.to_path_buf(),
+                source,
+            })?;
+
+        SceneBuilder { path }.build(description)
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct SceneDescription {
+    camera: CameraDescription,
+    renderer: RendererDescription,
+    materials: BTreeMap<String, MaterialDescription>,
+    objects: Vec<ObjectDescription>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct CameraDescription {
+    lookfrom: Option<[f64; 3]>,
+    lookat: Option<[f64; 3]>,
+    viewup: Option<[f64; 3]>,
+    vertical_fov: Option<f64>,
+    focus_dist: Option<f64>,
+    defocus_angle: Option<f64>,
+    shutter: Option<[f64; 2]>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RendererDescription {
+    width: Option<usize>,
+    height: Option<usize>,
+    aspect_ratio: Option<f64>,
+    samples_per_pixel: Option<usize>,
+    max_depth: Option<usize>,
+    roulette_depth: Option<usize>,
+    fading: Option<FadingDescription>,
+    background: Option<BackgroundDescription>,
+    seed: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum FadingDescription {
+    Const(f64),
+    Ramp([f64; 2]),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum BackgroundDescription {
+    Named(String),
+    Solid([f64; 3]),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum MaterialDescription {
+    Lambertian {
+        albedo: TextureDescription,
+    },
+    Metal {
+        albedo: TextureDescription,
+        #[serde(default)]
+        fuzz: f64,
+    },
+    Dielectric {
+        refraction_index: f64,
+    },
+    DiffuseLight {
+        emit: TextureDescription,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum TextureDescription {
+    Color([f64; 3]),
+    Texture(Box<TextureTable>),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum TextureTable {
+    Solid {
+        color: [f64; 3],
+    },
+    Checker {
+        scale: f64,
+        even: TextureDescription,
+        odd: TextureDescription,
+    },
+    Image {
+        path: PathBuf,
+    },
+    Noise {
+        scale: f64,
+        #[serde(default)]
+        mode: NoiseModeDescription,
+    },
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum NoiseModeDescription {
+    #[default]
+    Plain,
+    Turbulence,
+    Marble,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum ObjectDescription {
+    Sphere {
+        center: CenterDescription,
+        radius: f64,
+        material: String,
+    },
+    Triangle {
+        vertices: [[f64; 3]; 3],
+        material: String,
+    },
+    Quad {
+        q: [f64; 3],
+        u: [f64; 3],
+        v: [f64; 3],
+        material: String,
+    },
+    Disk {
+        center: [f64; 3],
+        normal: [f64; 3],
+        radius: f64,
+        material: String,
+    },
+    Ellipse {
+        center: [f64; 3],
+        u: [f64; 3],
+        v: [f64; 3],
+        material: String,
+    },
+    Annulus {
+        center: [f64; 3],
+        normal: [f64; 3],
+        radius: f64,
+        inner_radius: f64,
+        material: String,
+    },
+    Box {
+        a: [f64; 3],
+        b: [f64; 3],
+        material: String,
+    },
+    Obj {
+        path: PathBuf,
+        translate: Option<[f64; 3]>,
+        rotate: Option<RotationDescription>,
+        scale: Option<ScaleDescription>,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum CenterDescription {
+    Fixed([f64; 3]),
+    Keyframes(Vec<KeyframeDescription>),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeyframeDescription {
+    time: f64,
+    position: [f64; 3],
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RotationDescription {
+    axis: [f64; 3],
+    angle: f64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ScaleDescription {
+    Uniform(f64),
+    Axes([f64; 3]),
+}
+
+/// Geometry of an OBJ file, shared by all the instances of the model.
+struct SharedModel {
+    bvh: Arc<dyn Hittable>,
+
+    // Triangles of emissive materials, in the space of the model
+    lights: Vec<MeshTriangle<Arc<dyn Material>>>,
+}
+
+/// Turn a parsed description into a scene, validating it along the way.
+struct SceneBuilder<'a> {
+    path: &'a Path,
+}
+
+impl SceneBuilder<'_> {
+    fn build(&self, description: SceneDescription) -> Result<Scene, SceneError> {
+        let settings = &description.renderer;
+        let rng = &mut random::seeded(settings.seed.unwrap_or_default());
+
+        let (image_width, image_height) = self.image_size(settings)?;
+        let camera = self
+            .camera(&description.camera)?
+            .with_viewport_size(image_width, image_height);
+        let mut renderer = Renderer::new(camera, image_width, image_height);
+
+        if let Some(samples_per_pixel) = settings.samples_per_pixel {
+            if samples_per_pixel == 0 {
+                return Err(self.invalid("renderer.samples_per_pixel", "must be positive"));
+            }
+            renderer = renderer.with_samples_per_pixel(samples_per_pixel);
+        }
+        if let Some(max_depth) = settings.max_depth {
+            renderer = renderer.with_max_depth(max_depth);
+        }
+        if let Some(roulette_depth) = settings.roulette_depth {
+            renderer = renderer.with_roulette_depth(roulette_depth);
+        }
+        if let Some(seed) = settings.seed {
+            renderer = renderer.with_seed(seed);
+        }
+        if let Some(background) = &settings.background {
+            renderer = renderer.with_background(self.background(background)?);
+        }
+
+        let fading = match settings.fading {
+            None => Fading::Const(1.0),
+            Some(FadingDescription::Const(fading)) => Fading::Const(fading),
+            Some(FadingDescription::Ramp(fadings)) => Fading::Ramp(fadings),
+        };
+
+        let mut materials = HashMap::new();
+        for (name, material) in &description.materials {
+            let key = format!("materials.{name}");
+            materials.insert(name.as_str(), self.material(&key, material, rng)?);
+        }
+
+        let mut models = HashMap::new();
+        let mut world = Vec::new();
+        let mut lights = Vec::new();
+        for (index, object) in description.objects.iter().enumerate() {
+            let key = format!("objects[{index}]");
+            self.object(
+                &key,
+                object,
+                &materials,
+                &mut models,
+                &mut world,
+                &mut lights,
+            )?;
+        }
+        renderer = renderer.with_lights(lights);
+
+        Ok(Scene {
+            renderer,
+            world,
+            fading,
+        })
+    }
+
+    fn image_size(&self, settings: &RendererDescription) -> Result<(usize, usize), SceneError> {
+        let width = settings.width.unwrap_or(DEFAULT_IMAGE_WIDTH);
+        if width == 0 {
+            return Err(self.invalid("renderer.width", "must be positive"));
+        }
+
+        let height = match (settings.height, settings.aspect_ratio) {
+            (Some(_), Some(_)) => {
+                return Err(self.invalid(
+                    "renderer.height",
+                    "cannot be given along with `renderer.aspect_ratio`",
+                ))
+            },
+            (Some(height), None) => height,
+            (None, Some(aspect_ratio)) if aspect_ratio <= 0.0 => {
+                return Err(self.invalid("renderer.aspect_ratio", "must be positive"))
+            },
+            (None, aspect_ratio) => {
+                (width as f64 / aspect_ratio.unwrap_or(DEFAULT_ASPECT_RATIO)) as usize
+            },
+        };
+        if height == 0 {
+            return Err(self.invalid("renderer.height", "must be positive"));
+        }
+
+        Ok((width, height))
+    }
+
+    fn camera(&self, description: &CameraDescription) -> Result<Camera, SceneError> {
+        let mut camera = Camera::default();
+
+        if let Some(lookfrom) = description.lookfrom {
+            camera = camera.with_lookfrom(Point3::from(lookfrom));
+        }
+        if let Some(lookat) = description.lookat {
+            camera = camera.with_lookat(Point3::from(lookat));
+        }
+        if let Some(viewup) = description.viewup {
+            camera = camera.with_viewup(Vector3::from(viewup));
+        }
+        if let Some(vertical_fov) = description.vertical_fov {
+            if !(vertical_fov > 0.0 && vertical_fov < 180.0) {
+                return Err(self.invalid("camera.vertical_fov", "must be between 0 and 180"));
+            }
+            camera = camera.with_vertical_fov(vertical_fov);
+        }
+        if let Some(focus_dist) = description.focus_dist {
+            if focus_dist <= 0.0 {
+                return Err(self.invalid("camera.focus_dist", "must be positive"));
+            }
+            camera = camera.with_focus_dist(focus_dist);
+        }
+        if let Some(defocus_angle) = description.defocus_angle {
+            camera = camera.with_defocus_angle(defocus_angle);
+        }
+        if let Some([open, close]) = description.shutter {
+            if close < open {
+                return Err(self.invalid("camera.shutter", "must not close before opening"));
+            }
+            camera = camera.with_shutter(open, close);
+        }
+
+        Ok(camera)
+    }
+
+    fn background(&self, description: &BackgroundDescription) -> Result<Background, SceneError> {
+        match description {
+            BackgroundDescription::Named(name) if name == "sky" => Ok(Background::Sky),
+            BackgroundDescription::Named(name) => Err(self.invalid(
+                "renderer.background",
+                format!("unknown background `{name}`, expected `sky` or a color"),
+            )),
+            BackgroundDescription::Solid(color) => Ok(Background::Solid(to_color(color))),
+        }
+    }
+
+    fn material(
+        &self,
+        key: &str,
+        description: &MaterialDescription,
+        rng: &mut Rng,
+    ) -> Result<Arc<dyn Material>, SceneError> {
+        Ok(match description {
+            MaterialDescription::Lambertian { albedo } => {
+                let albedo = self.texture(&format!("{key}.albedo"), albedo, rng)?;
+                Arc::new(Lambertian::from_texture(albedo))
+            },
+            MaterialDescription::Metal { albedo, fuzz } => {
+                if !(0.0..=1.0).contains(fuzz) {
+                    return Err(self.invalid(format!("{key}.fuzz"), "must be between 0 and 1"));
+                }
+                let albedo = self.texture(&format!("{key}.albedo"), albedo, rng)?;
+                Arc::new(Metal::from_texture(albedo, *fuzz))
+            },
+            MaterialDescription::Dielectric { refraction_index } => {
+                if *refraction_index <= 0.0 {
+                    return Err(self.invalid(format!("{key}.refraction_index"), "must be positive"));
+                }
+                Arc::new(Dielectric::new(*refraction_index))
+            },
+            MaterialDescription::DiffuseLight { emit } => {
+                let emit = self.texture(&format!("{key}.emit"), emit, rng)?;
+                Arc::new(DiffuseLight::from_texture(emit))
+            },
+        })
+    }
+
+    fn texture(
+        &self,
+        key: &str,
+        description: &TextureDescription,
+        rng: &mut Rng,
+    ) -> Result<Arc<dyn Texture>, SceneError> {
+        let table = match description {
+            TextureDescription::Color(color) => {
+                return Ok(Arc::new(SolidColor::new(to_color(color))));
+            },
+            TextureDescription::Texture(table) => table,
+        };
+
+        Ok(match table.as_ref() {
+            TextureTable::Solid { color } => Arc::new(SolidColor::new(to_color(color))),
+            TextureTable::Checker { scale, even, odd } => {
+                if *scale <= 0.0 {
+                    return Err(self.invalid(format!("{key}.scale"), "must be positive"));
+                }
+                let even = self.texture(&format!("{key}.even"), even, rng)?;
+                let odd = self.texture(&format!("{key}.odd"), odd, rng)?;
+                Arc::new(Checker::from_textures(*scale, even, odd))
+            },
+            TextureTable::Image { path } => {
+                let texture = ImageTexture::load(self.resolve(path))
+                    .map_err(|err| self.invalid(format!("{key}.path"), err.to_string()))?;
+                Arc::new(texture)
+            },
+            TextureTable::Noise { scale, mode } => {
+                if *scale <= 0.0 {
+                    return Err(self.invalid(format!("{key}.scale"), "must be positive"));
+                }
+                let mode = match mode {
+                    NoiseModeDescription::Plain => NoiseMode::Plain,
+                    NoiseModeDescription::Turbulence => NoiseMode::Turbulence,
+                    NoiseModeDescription::Marble => NoiseMode::Marble,
+                };
+                Arc::new(NoiseTexture::new(*scale, mode, rng))
+            },
+        })
+    }
+
+    fn object(
+        &self,
+        key: &str,
+        description: &ObjectDescription,
+        materials: &HashMap<&str, Arc<dyn Material>>,
+        models: &mut HashMap<PathBuf, SharedModel>,
+        world: &mut Vec<Box<dyn Hittable>>,
+        lights: &mut Vec<Box<dyn Light>>,
+    ) -> Result<(), SceneError> {
+        let material = |name: &String| {
+            materials.get(name.as_str()).cloned().ok_or_else(|| {
+                self.invalid(
+                    format!("{key}.material"),
+                    format!("undefined material `{name}`"),
+                )
+            })
+        };
+
+        match description {
+            ObjectDescription::Sphere {
+                center,
+                radius,
+                material: name,
+            } => {
+                if *radius <= 0.0 {
+                    return Err(self.invalid(format!("{key}.radius"), "must be positive"));
+                }
+                let center = match center {
+                    CenterDescription::Fixed(center) => Motion::Fixed(Point3::from(*center)),
+                    CenterDescription::Keyframes(keyframes) if keyframes.is_empty() => {
+                        return Err(self.invalid(format!("{key}.center"), "must not be empty"));
+                    },
+                    CenterDescription::Keyframes(keyframes) => Motion::keyframes(
+                        keyframes
+                            .iter()
+                            .map(|keyframe| (keyframe.time, Point3::from(keyframe.position)))
+                            .collect(),
+                    ),
+                };
+                let sphere = Sphere::moving(center, *radius, material(name)?);
+                let emissive = sphere.material().is_emissive();
+                add_object(sphere, emissive, world, lights);
+            },
+            ObjectDescription::Triangle {
+                vertices: [a, b, c],
+                material: name,
+            } => {
+                let triangle = Triangle::new(
+                    Point3::from(*a),
+                    Point3::from(*b),
+                    Point3::from(*c),
+                    material(name)?,
+                );
+                let emissive = triangle.material().is_emissive();
+                add_object(triangle, emissive, world, lights);
+            },
+            ObjectDescription::Quad {
+                q,
+                u,
+                v,
+                material: name,
+            } => {
+                let (u, v) = (Vector3::from(*u), Vector3::from(*v));
+                if u.cross(&v) == Vector3::zeros() {
+                    return Err(self.invalid(
+                        format!("{key}.v"),
+                        "must be nonzero and not parallel to `u`",
+                    ));
+                }
+                let quad = Quad::new(Point3::from(*q), u, v, material(name)?);
+                let emissive = quad.material().is_emissive();
+                add_object(quad, emissive, world, lights);
+            },
+            ObjectDescription::Disk {
+                center,
+                normal,
+                radius,
+                material: name,
+            } => {
+                if *radius <= 0.0 {
+                    return Err(self.invalid(format!("{key}.radius"), "must be positive"));
+                }
+                let normal = Vector3::from(*normal);
+                if normal == Vector3::zeros() {
+                    return Err(self.invalid(format!("{key}.normal"), "must be nonzero"));
+                }
+                let disk = Planar::disk(Point3::from(*center), normal, *radius, material(name)?);
+                let emissive = disk.material().is_emissive();
+                add_object(disk, emissive, world, lights);
+            },
+            ObjectDescription::Ellipse {
+                center,
+                u,
+                v,
+                material: name,
+            } => {
+                let (u, v) = (Vector3::from(*u), Vector3::from(*v));
+                if u.cross(&v) == Vector3::zeros() {
+                    return Err(self.invalid(
+                        format!("{key}.v"),
+                        "must be nonzero and not parallel to `u`",
+                    ));
+                }
+                let ellipse = Planar::ellipse(Point3::from(*center), u, v, material(name)?);
+                let emissive = ellipse.material().is_emissive();
+                add_object(ellipse, emissive, world, lights);
+            },
+            ObjectDescription::Annulus {
+                center,
+                normal,
+                radius,
+                inner_radius,
+                material: name,
+            } => {
+                if *radius <= 0.0 {
+                    return Err(self.invalid(format!("{key}.radius"), "must be positive"));
+                }
+                if !(0.0..*radius).contains(inner_radius) {
+                    return Err(self.invalid(
+                        format!("{key}.inner_radius"),
+                        "must be positive and less than the radius",
+                    ));
+                }
+                let normal = Vector3::from(*normal);
+                if normal == Vector3::zeros() {
+                    return Err(self.invalid(format!("{key}.normal"), "must be nonzero"));
+                }
+                let annulus = Planar::annulus(
+                    Point3::from(*center),
+                    normal,
+                    *radius,
+                    *inner_radius,
+                    material(name)?,
+                );
+                let emissive = annulus.material().is_emissive();
+                add_object(annulus, emissive, world, lights);
+            },
+            ObjectDescription::Box {
+                a,
+                b,
+                material: name,
+            } => {
+                let material = material(name)?;
+                let emissive = material.is_emissive();
+                for side in make_box(Point3::from(*a), Point3::from(*b), material).into_objects() {
+                    add_object(side, emissive, world, lights);
+                }
+            },
+            ObjectDescription::Obj {
+                path,
+                translate,
+                rotate,
+                scale,
+            } => {
+                // Models are instances sharing a hierarchy per file, even when not transformed.
+                let model = match models.entry(self.resolve(path)) {
+                    Entry::Occupied(entry) => entry.into_mut(),
+                    Entry::Vacant(entry) => {
+                        let triangles = ObjModel::load(entry.key())
+                            .map_err(SceneError::Obj)?
+                            .into_mesh_triangles();
+                        let lights = triangles
+                            .iter()
+                            .filter(|triangle| triangle.material().is_emissive())
+                            .cloned()
+                            .collect();
+                        let bvh = Bvh::new(
+                            triangles
+                                .into_iter()
+                                .map(|triangle| Box::new(triangle) as Box<dyn Hittable>)
+                                .collect(),
+                        );
+                        entry.insert(SharedModel {
+                            bvh: Arc::new(bvh),
+                            lights,
+                        })
+                    },
+                };
+
+                let mut instance = Instance::new(Arc::clone(&model.bvh), Affine3::identity());
+                if let Some(scale) = scale {
+                    let factors = match scale {
+                        ScaleDescription::Uniform(factor) => Vector3::repeat(*factor),
+                        ScaleDescription::Axes(factors) => Vector3::from(*factors),
+                    };
+                    if factors.iter().any(|&factor| factor == 0.0) {
+                        return Err(self.invalid(format!("{key}.scale"), "must be nonzero"));
+                    }
+                    instance = instance.scaled(factors);
+                }
+                if let Some(RotationDescription { axis, angle }) = rotate {
+                    let axis = Vector3::from(*axis);
+                    if axis == Vector3::zeros() {
+                        return Err(self.invalid(format!("{key}.rotate.axis"), "must be nonzero"));
+                    }
+                    instance = instance.rotated(axis, *angle);
+                }
+                if let Some(translate) = translate {
+                    instance = instance.translated(Vector3::from(*translate));
+                }
+
+                // Instances are not lights, so their emissive triangles are sampled through
+                // copies placed in world space.
+                for triangle in &model.lights {
+                    lights.push(Box::new(triangle.transformed(instance.transform())));
+                }
+                world.push(Box::new(instance));
+            },
+        }
+
+        Ok(())
+    }
+
+    /// Resolve a path relatively to the directory of the scene file.
+    fn resolve(&self, path: &Path) -> PathBuf {
+        self.path.parent().unwrap_or(Path::new("")).join(path)
+    }
+
+    fn invalid(&self, key: impl Into<String>, message: impl Into<String>) -> SceneError {
+        SceneError::Invalid {
+            path: self.path.to_path_buf(),
+            key: key.into(),
+            message: message.into(),
+        }
+    }
+}
+
+/// Add `object` to the world, and to the lights too if it is `emissive`.
+fn add_object<T: Hittable + Light + Clone + 'static>(
+    object: T,
+    emissive: bool,
+    world: &mut Vec<Box<dyn Hittable>>,
+    lights: &mut Vec<Box<dyn Light>>,
+) {
+    if emissive {
+        lights.push(Box::new(object.clone()));
+    }
+    world.push(Box::new(object));
+}
+
+fn to_color([r, g, b]: &[f64; 3]) -> Color {
+    Color::new(*r, *g, *b)
+}
@@ -1,28 +1,43 @@
+use std::f64::consts::PI;
 use std::ops::Range;
 
+use crate::aabb::Aabb;
+use crate::light::{Light, LightSample};
 use crate::material::Material;
+use crate::motion::Motion;
 use crate::object::{Hit, Hittable};
-use crate::types::{Point3, Ray};
+use crate::types::{Basis, Point2, Point3, Ray, Vector3};
 
+#[derive(Clone)]
 pub struct Sphere<M> {
-    center: Point3,
+    center: Motion,
     radius: f64,
     material: M,
 }
 
 impl<M: Material> Sphere<M> {
     pub fn new(center: Point3, radius: f64, material: M) -> Self {
+        Self::moving(Motion::Fixed(center), radius, material)
+    }
+
+    /// Sphere whose center follows `center` over time.
+    pub fn moving(center: Motion, radius: f64, material: M) -> Self {
         Self {
             center,
             radius: radius.max(0.0),
             material,
         }
     }
+
+    pub fn material(&self) -> &M {
+        &self.material
+    }
 }
 
 impl<M: Material> Hittable for Sphere<M> {
-    fn hit(&self, ray: &Ray, t_range: Range<f64>) -> Option<Hit> {
-        let oc = self.center - ray.origin;
+    fn hit(&self, ray: &Ray, t_range: Range<f64>) -> Option<Hit<'_>> {
+        let center = self.center.at(ray.time);
+        let oc = center - ray.origin;
         let a = ray.direction.norm_squared();
         let h = ray.direction.dot(&oc);
         let c = oc.norm_squared() - self.radius.powi(2);
@@ -35,16 +50,81 @@ impl<M: Material> Hittable for Sphere<M> {
             for root in [(h - sqrt) / a, (h + sqrt) / a] {
                 if t_range.contains(&root) {
                     let point = ray.at(root);
-                    return Some(Hit::new(
-                        root,
-                        point,
-                        ray,
-                        (point - self.center) / self.radius,
-                        &self.material,
-                    ));
+                    let outward_normal = (point - center) / self.radius;
+                    let hit = Hit::new(root, point, ray, outward_normal, &self.material)
+                        .with_uv(sphere_uv(&outward_normal));
+                    return Some(hit);
                 }
             }
         }
         None
     }
+
+    fn bounding_box(&self) -> Aabb {
+        let bbox = self.center.bounding_box();
+        let radius = Vector3::repeat(self.radius);
+        Aabb::new(bbox.min - radius, bbox.max + radius)
+    }
+}
+
+impl<M: Material> Light for Sphere<M> {
+    /// Sample a direction uniformly within the cone of directions subtended by the sphere. Points
+    /// inside the sphere cannot be sampled from.
+    fn sample(&self, origin: &Point3, time: f64, u: Point2) -> Option<LightSample<'_>> {
+        let to_center = self.center.at(time) - origin;
+        let distance_squared = to_center.norm_squared();
+        let sin_theta_max_squared = self.radius.powi(2) / distance_squared;
+        if sin_theta_max_squared >= 1.0 {
+            return None;
+        }
+
+        let one_minus_cos_theta_max = one_minus_cos(sin_theta_max_squared);
+        let cos_theta = 1.0 - u.x * one_minus_cos_theta_max;
+        let sin_theta = (1.0 - cos_theta.powi(2)).max(0.0).sqrt();
+        let phi = 2.0 * PI * u.y;
+        let local = Vector3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+        let direction = Basis::from_w(to_center.normalize()).to_world(&local);
+
+        let hit = self.hit(
+            &Ray::new(*origin, direction).with_time(time),
+            0.0..f64::INFINITY,
+        )?;
+        Some(LightSample {
+            hit,
+            direction,
+            pdf: 1.0 / (2.0 * PI * one_minus_cos_theta_max),
+        })
+    }
+
+    fn pdf(&self, origin: &Point3, time: f64, direction: &Vector3) -> f64 {
+        let to_center = self.center.at(time) - origin;
+        let sin_theta_max_squared = self.radius.powi(2) / to_center.norm_squared();
+        if sin_theta_max_squared >= 1.0
+            || self
+                .hit(
+                    &Ray::new(*origin, *direction).with_time(time),
+                    0.0..f64::INFINITY,
+                )
+                .is_none()
+        {
+            return 0.0;
+        }
+
+        1.0 / (2.0 * PI * one_minus_cos(sin_theta_max_squared))
+    }
+}
+
+/// `1 - cos(theta)` from `sin(theta)²`, written so that it does not cancel out for small angles,
+/// such as the ones subtended by small or far spheres.
+fn one_minus_cos(sin_squared: f64) -> f64 {
+    sin_squared / (1.0 + (1.0 - sin_squared).sqrt())
+}
+
+/// Surface coordinates of a point on the unit sphere centered at the origin: `u` is the angle
+/// around the Y axis from X=-1, and `v` the angle from Y=-1 to Y=+1, both normalized to 0..1.
+fn sphere_uv(point: &Point3) -> Point2 {
+    let theta = (-point.y).acos();
+    let phi = (-point.z).atan2(point.x) + PI;
+
+    Point2::new(phi / (2.0 * PI), theta / PI)
 }
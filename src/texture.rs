@@ -0,0 +1,251 @@
+use std::fmt;
+use std::ops::Deref;
+use std::path::Path;
+
+use rand::seq::SliceRandom;
+
+use crate::random::Rng;
+use crate::types::{random_unit_vector_on_sphere, Color, Point2, Point3, Vector3};
+
+pub trait Texture: fmt::Debug + Send + Sync {
+    /// Color of the texture at surface coordinates `uv` of the hit `point`.
+    fn value(&self, uv: &Point2, point: &Point3) -> Color;
+}
+
+impl<T> Texture for T
+where
+    T: Deref<Target = dyn Texture> + fmt::Debug + Send + Sync,
+{
+    fn value(&self, uv: &Point2, point: &Point3) -> Color {
+        self.deref().value(uv, point)
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct SolidColor {
+    albedo: Color,
+}
+
+impl SolidColor {
+    pub fn new(albedo: Color) -> Self {
+        Self { albedo }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _uv: &Point2, _point: &Point3) -> Color {
+        self.albedo
+    }
+}
+
+/// 3D checker pattern alternating between two textures in cubes of side `scale`.
+#[derive(Clone, Debug)]
+pub struct Checker<E = SolidColor, O = SolidColor> {
+    inv_scale: f64,
+    even: E,
+    odd: O,
+}
+
+impl Checker {
+    pub fn new(scale: f64, even: Color, odd: Color) -> Self {
+        Self::from_textures(scale, SolidColor::new(even), SolidColor::new(odd))
+    }
+}
+
+impl<E: Texture, O: Texture> Checker<E, O> {
+    pub fn from_textures(scale: f64, even: E, odd: O) -> Self {
+        Self {
+            inv_scale: 1.0 / scale,
+            even,
+            odd,
+        }
+    }
+}
+
+impl<E: Texture, O: Texture> Texture for Checker<E, O> {
+    fn value(&self, uv: &Point2, point: &Point3) -> Color {
+        let cell = (self.inv_scale * point).map(|x| x.floor() as i64);
+
+        if (cell.x + cell.y + cell.z) % 2 == 0 {
+            self.even.value(uv, point)
+        } else {
+            self.odd.value(uv, point)
+        }
+    }
+}
+
+/// Texture mapping an image onto the surface coordinates, `v` going from the bottom to the top
+/// of the image.
+#[derive(Clone, Debug)]
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+}
+
+impl ImageTexture {
+    /// Load a PNG or JPEG image, which must not be empty.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, image::ImageError> {
+        let image = image::open(path)?.into_rgb8();
+        let (width, height) = (image.width() as usize, image.height() as usize);
+
+        // Lookups assume there is at least one pixel.
+        if width == 0 || height == 0 {
+            return Err(image::ImageError::Limits(
+                image::error::LimitError::from_kind(image::error::LimitErrorKind::DimensionError),
+            ));
+        }
+
+        // Image files are sRGB encoded, the renderer works with linear colors.
+        let pixels = image
+            .pixels()
+            .map(|pixel| {
+                let [r, g, b] = pixel.0.map(|c| c as f64 / 255.0);
+                Color::new(r, g, b).to_linear()
+            })
+            .collect();
+
+        Ok(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, uv: &Point2, _point: &Point3) -> Color {
+        let u = uv.x.clamp(0.0, 1.0);
+        let v = 1.0 - uv.y.clamp(0.0, 1.0);
+        let i = ((u * self.width as f64) as usize).min(self.width - 1);
+        let j = ((v * self.height as f64) as usize).min(self.height - 1);
+
+        self.pixels[i + j * self.width]
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum NoiseMode {
+    /// Smooth Perlin noise.
+    Plain,
+
+    /// Sum of noise octaves of decreasing amplitude.
+    Turbulence,
+
+    /// Stripes along z distorted by turbulence.
+    Marble,
+}
+
+#[derive(Clone, Debug)]
+pub struct NoiseTexture {
+    perlin: Perlin,
+    scale: f64,
+    mode: NoiseMode,
+}
+
+impl NoiseTexture {
+    pub fn new(scale: f64, mode: NoiseMode, rng: &mut Rng) -> Self {
+        Self {
+            perlin: Perlin::new(rng),
+            scale,
+            mode,
+        }
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _uv: &Point2, point: &Point3) -> Color {
+        let intensity = match self.mode {
+            NoiseMode::Plain => 0.5 * (1.0 + self.perlin.noise(&(self.scale * point))),
+            NoiseMode::Turbulence => self.perlin.turbulence(&(self.scale * point), 7),
+            NoiseMode::Marble => {
+                0.5 * (1.0 + (self.scale * point.z + 10.0 * self.perlin.turbulence(point, 7)).sin())
+            },
+        };
+
+        intensity * Color::WHITE
+    }
+}
+
+const PERLIN_POINT_COUNT: usize = 256;
+
+/// Perlin gradient noise over a lattice of random unit vectors.
+#[derive(Clone, Debug)]
+pub struct Perlin {
+    gradients: Vec<Vector3>,
+    permutations: [Vec<usize>; 3],
+}
+
+impl Perlin {
+    pub fn new(rng: &mut Rng) -> Self {
+        let gradients = (0..PERLIN_POINT_COUNT)
+            .map(|_| random_unit_vector_on_sphere(rng))
+            .collect();
+        let permutations = [(); 3].map(|_| permutation(rng));
+
+        Self {
+            gradients,
+            permutations,
+        }
+    }
+
+    /// Noise value in -1..1 at `point`.
+    pub fn noise(&self, point: &Point3) -> f64 {
+        let floor = point.map(f64::floor);
+        let fract = point - floor;
+        // Hermite cubic smoothing of the interpolation weights.
+        let weight = fract.map(|t| t * t * (3.0 - 2.0 * t));
+
+        let mut accum = 0.0;
+        for di in 0..2 {
+            for dj in 0..2 {
+                for dk in 0..2 {
+                    let corner = Vector3::new(di as f64, dj as f64, dk as f64);
+                    let gradient = &self.gradients[self.hash(&floor, di, dj, dk)];
+                    let factor = (0..3)
+                        .map(|axis| {
+                            if corner[axis] > 0.0 {
+                                weight[axis]
+                            } else {
+                                1.0 - weight[axis]
+                            }
+                        })
+                        .product::<f64>();
+
+                    accum += factor * gradient.dot(&(fract - corner));
+                }
+            }
+        }
+
+        accum
+    }
+
+    /// Absolute value of the sum of `depth` noise octaves, each one at twice the frequency and
+    /// half the amplitude of the previous one.
+    pub fn turbulence(&self, point: &Point3, depth: usize) -> f64 {
+        let mut accum = 0.0;
+        let mut point = *point;
+        let mut weight = 1.0;
+
+        for _ in 0..depth {
+            accum += weight * self.noise(&point);
+            weight *= 0.5;
+            point *= 2.0;
+        }
+
+        accum.abs()
+    }
+
+    fn hash(&self, floor: &Point3, di: usize, dj: usize, dk: usize) -> usize {
+        let [x, y, z] = &self.permutations;
+        let index = |value: f64, offset: usize| (value as i64 + offset as i64) as usize & 255;
+
+        x[index(floor.x, di)] ^ y[index(floor.y, dj)] ^ z[index(floor.z, dk)]
+    }
+}
+
+fn permutation(rng: &mut Rng) -> Vec<usize> {
+    let mut permutation: Vec<usize> = (0..PERLIN_POINT_COUNT).collect();
+    permutation.shuffle(rng);
+    permutation
+}
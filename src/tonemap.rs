@@ -0,0 +1,337 @@
+use std::fmt;
+use std::sync::{Arc, OnceLock};
+
+use crate::random;
+use crate::types::{Color, Vector3};
+
+/// Operator compressing linear radiance into the 0..=1 range of displays. Whatever it leaves
+/// outside of this range is clipped.
+pub trait ToneMap: fmt::Debug + Send + Sync {
+    fn map(&self, color: Color) -> Color;
+}
+
+/// Leave the radiance as is, clipping everything above 1.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Clamp;
+
+impl ToneMap for Clamp {
+    fn map(&self, color: Color) -> Color {
+        color
+    }
+}
+
+/// Extended Reinhard operator on luminance, mapping a luminance of `white` and above to 1.
+///
+/// An infinite `white` gives the plain `L / (1 + L)` operator.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Reinhard {
+    white: f64,
+}
+
+impl Reinhard {
+    pub fn new(white: f64) -> Self {
+        Self { white }
+    }
+}
+
+impl Default for Reinhard {
+    fn default() -> Self {
+        Self::new(f64::INFINITY)
+    }
+}
+
+impl ToneMap for Reinhard {
+    fn map(&self, color: Color) -> Color {
+        let luminance = color.luminance();
+        if luminance <= 0.0 {
+            return Color::BLACK;
+        }
+
+        let mapped = luminance * (1.0 + luminance / self.white.powi(2)) / (1.0 + luminance);
+        (mapped / luminance) * color
+    }
+}
+
+/// John Hable's filmic curve from Uncharted 2, applied per channel and scaled so that `white`
+/// maps to 1.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Hable {
+    white: f64,
+}
+
+impl Hable {
+    pub fn new(white: f64) -> Self {
+        Self { white }
+    }
+
+    fn curve(x: f64) -> f64 {
+        const A: f64 = 0.15; // Shoulder strength
+        const B: f64 = 0.50; // Linear strength
+        const C: f64 = 0.10; // Linear angle
+        const D: f64 = 0.20; // Toe strength
+        const E: f64 = 0.02; // Toe numerator
+        const F: f64 = 0.30; // Toe denominator
+
+        (x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F) - E / F
+    }
+}
+
+impl Default for Hable {
+    fn default() -> Self {
+        Self::new(11.2)
+    }
+}
+
+impl ToneMap for Hable {
+    fn map(&self, color: Color) -> Color {
+        let scale = 1.0 / Self::curve(self.white);
+        Color(color.map(|c| scale * Self::curve(c.max(0.0))))
+    }
+}
+
+// Linear sRGB to ACES AP1, including the saturation adjustment of the reference rendering
+// transform, and back.
+const ACES_INPUT: [Vector3; 3] = [
+    Vector3::new(0.59719, 0.35458, 0.04823),
+    Vector3::new(0.07600, 0.90834, 0.01566),
+    Vector3::new(0.02840, 0.13383, 0.83777),
+];
+const ACES_OUTPUT: [Vector3; 3] = [
+    Vector3::new(1.60475, -0.53108, -0.07367),
+    Vector3::new(-0.10208, 1.10813, -0.00605),
+    Vector3::new(-0.00327, -0.07276, 1.07602),
+];
+
+/// Stephen Hill's fit of the ACES reference rendering and sRGB output device transforms.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Aces;
+
+impl ToneMap for Aces {
+    fn map(&self, color: Color) -> Color {
+        let transform = |rows: &[Vector3; 3], v: &Vector3| {
+            Vector3::new(rows[0].dot(v), rows[1].dot(v), rows[2].dot(v))
+        };
+
+        let v = transform(&ACES_INPUT, &color);
+        let v = v.map(|v| {
+            let a = v * (v + 0.0245786) - 0.000090537;
+            let b = v * (0.983729 * v + 0.4329510) + 0.238081;
+            a / b
+        });
+        Color(transform(&ACES_OUTPUT, &v))
+    }
+}
+
+/// Encoding of the tone mapped values.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Transfer {
+    /// Piecewise sRGB transfer function.
+    Srgb,
+
+    /// Plain power law with the given gamma.
+    Gamma(f64),
+}
+
+/// Noise added before quantization, trading banding in smooth gradients for fine grain.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Dither {
+    /// Round to the nearest level.
+    None,
+
+    /// 8×8 Bayer matrix thresholds.
+    Ordered,
+
+    /// 64×64 void-and-cluster blue noise thresholds.
+    BlueNoise,
+}
+
+/// Conversion of linear radiance to display values: exposure, tone mapping, transfer function
+/// and quantization.
+#[derive(Clone, Debug)]
+pub struct DisplayTransform {
+    exposure: f64,
+    tone_map: Arc<dyn ToneMap>,
+    transfer: Transfer,
+    dither: Dither,
+}
+
+impl Default for DisplayTransform {
+    fn default() -> Self {
+        Self {
+            exposure: 0.0,
+            tone_map: Arc::new(Clamp),
+            transfer: Transfer::Srgb,
+            dither: Dither::None,
+        }
+    }
+}
+
+impl DisplayTransform {
+    /// Scale the radiance by 2 to the power of `stops` before tone mapping.
+    pub fn with_exposure(mut self, stops: f64) -> Self {
+        self.exposure = stops;
+        self
+    }
+
+    pub fn with_tone_map(mut self, tone_map: impl ToneMap + 'static) -> Self {
+        self.tone_map = Arc::new(tone_map);
+        self
+    }
+
+    pub fn with_transfer(mut self, transfer: Transfer) -> Self {
+        self.transfer = transfer;
+        self
+    }
+
+    pub fn with_dither(mut self, dither: Dither) -> Self {
+        self.dither = dither;
+        self
+    }
+
+    /// Encoded display value in the 0..=1 range of a linear `color`.
+    pub fn apply(&self, color: Color) -> Color {
+        let mapped = self.tone_map.map(self.exposure.exp2() * color).clamp();
+
+        match self.transfer {
+            Transfer::Srgb => mapped.to_srgb(),
+            Transfer::Gamma(gamma) => Color(mapped.map(|c| c.powf(1.0 / gamma))),
+        }
+    }
+
+    /// Display value of `color` at pixel `i`, `j` as 8-bit integers.
+    pub fn to_u8(&self, color: Color, i: usize, j: usize) -> [u8; 3] {
+        self.quantize(color, i, j, u8::MAX.into()).map(|c| c as u8)
+    }
+
+    /// Display value of `color` at pixel `i`, `j` as 16-bit integers.
+    pub fn to_u16(&self, color: Color, i: usize, j: usize) -> [u16; 3] {
+        self.quantize(color, i, j, u16::MAX)
+    }
+
+    fn quantize(&self, color: Color, i: usize, j: usize, max: u16) -> [u16; 3] {
+        let threshold = match self.dither {
+            Dither::None => 0.5,
+            Dither::Ordered => bayer_threshold(i, j),
+            Dither::BlueNoise => blue_noise_threshold(i, j),
+        };
+
+        let value = self.apply(color);
+        [value.x, value.y, value.z].map(|c| (c * max as f64 + threshold).floor() as u16)
+    }
+}
+
+/// Threshold in 0..1 of the 8×8 Bayer matrix tiled over the image.
+fn bayer_threshold(i: usize, j: usize) -> f64 {
+    // Each level of the recursive construction contributes a base 4 digit, the coarsest level
+    // the least significant one.
+    let mut index = 0;
+    for bit in 0..3 {
+        let (x, y) = ((i >> bit) & 1, (j >> bit) & 1);
+        index = 4 * index + (2 * (x ^ y) + y);
+    }
+
+    (index as f64 + 0.5) / 64.0
+}
+
+const BLUE_NOISE_SIZE: usize = 64;
+
+/// Threshold in 0..1 of the blue noise mask tiled over the image.
+fn blue_noise_threshold(i: usize, j: usize) -> f64 {
+    static MASK: OnceLock<Vec<f64>> = OnceLock::new();
+    let mask = MASK.get_or_init(void_and_cluster);
+
+    mask[i % BLUE_NOISE_SIZE + (j % BLUE_NOISE_SIZE) * BLUE_NOISE_SIZE]
+}
+
+/// Ulichney's void-and-cluster method: rank the cells of a toroidal grid so that the cells of
+/// any rank below a threshold are evenly spread out, and turn the ranks into thresholds.
+fn void_and_cluster() -> Vec<f64> {
+    const SIZE: usize = BLUE_NOISE_SIZE;
+    const SIGMA: f64 = 1.5;
+    let len = SIZE * SIZE;
+
+    // Energy contributed by a point to the cells at each toroidal offset from it.
+    let kernel: Vec<f64> = (0..len)
+        .map(|offset| {
+            let (dx, dy) = (offset % SIZE, offset / SIZE);
+            let (dx, dy) = (dx.min(SIZE - dx) as f64, dy.min(SIZE - dy) as f64);
+            (-(dx * dx + dy * dy) / (2.0 * SIGMA * SIGMA)).exp()
+        })
+        .collect();
+
+    #[derive(Clone)]
+    struct Pattern<'a> {
+        kernel: &'a [f64],
+        points: Vec<bool>,
+        energy: Vec<f64>,
+    }
+
+    impl Pattern<'_> {
+        fn set(&mut self, index: usize, point: bool) {
+            self.points[index] = point;
+            let sign = if point { 1.0 } else { -1.0 };
+            let (x, y) = (index % SIZE, index / SIZE);
+            for (cell, energy) in self.energy.iter_mut().enumerate() {
+                let dx = (cell % SIZE + SIZE - x) % SIZE;
+                let dy = (cell / SIZE + SIZE - y) % SIZE;
+                *energy += sign * self.kernel[dx + dy * SIZE];
+            }
+        }
+
+        /// Point with the most energy, or empty cell with the least.
+        fn extremum(&self, point: bool) -> usize {
+            let cells = (0..self.points.len()).filter(|&cell| self.points[cell] == point);
+            let by_energy = |&a: &usize, &b: &usize| self.energy[a].total_cmp(&self.energy[b]);
+            if point {
+                cells.max_by(by_energy).unwrap()
+            } else {
+                cells.min_by(by_energy).unwrap()
+            }
+        }
+    }
+
+    let mut initial = Pattern {
+        kernel: &kernel,
+        points: vec![false; len],
+        energy: vec![0.0; len],
+    };
+    let rng = &mut random::seeded(0);
+    for index in rand::seq::index::sample(rng, len, len / 10) {
+        initial.set(index, true);
+    }
+
+    // Move the point of the tightest cluster to the largest void until it stays in place.
+    for _ in 0..len {
+        let cluster = initial.extremum(true);
+        initial.set(cluster, false);
+        let void = initial.extremum(false);
+        initial.set(void, true);
+        if void == cluster {
+            break;
+        }
+    }
+
+    let ones = initial.points.iter().filter(|&&point| point).count();
+    let mut ranks = vec![0; len];
+
+    // Rank the initial points by removing the tightest clusters first, then fill the largest
+    // voids first.
+    let mut pattern = initial.clone();
+    for rank in (0..ones).rev() {
+        let cluster = pattern.extremum(true);
+        pattern.set(cluster, false);
+        ranks[cluster] = rank;
+    }
+
+    let mut pattern = initial;
+    for rank in ones..len {
+        let void = pattern.extremum(false);
+        pattern.set(void, true);
+        ranks[void] = rank;
+    }
+
+    ranks
+        .into_iter()
+        .map(|rank| (rank as f64 + 0.5) / len as f64)
+        .collect()
+}
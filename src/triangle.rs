@@ -0,0 +1,361 @@
+use std::ops::Range;
+use std::sync::Arc;
+
+use nalgebra::Affine3;
+
+use crate::aabb::Aabb;
+use crate::light::{Light, LightSample};
+use crate::material::Material;
+use crate::object::{Hit, Hittable};
+use crate::types::{Point2, Point3, Ray, Vector3};
+
+// Thickness given to the bounding box of triangles lying in an axis-aligned plane.
+const BBOX_PADDING: f64 = 1e-4;
+
+#[derive(Clone)]
+pub struct Triangle<M> {
+    vertices: [Point3; 3],
+    material: M,
+}
+
+impl<M: Material> Triangle<M> {
+    pub fn new(a: Point3, b: Point3, c: Point3, material: M) -> Self {
+        Self {
+            vertices: [a, b, c],
+            material,
+        }
+    }
+
+    pub fn material(&self) -> &M {
+        &self.material
+    }
+}
+
+impl<M: Material> Hittable for Triangle<M> {
+    fn hit(&self, ray: &Ray, t_range: Range<f64>) -> Option<Hit<'_>> {
+        let [a, b, c] = &self.vertices;
+        let (t, barycentric) = intersect([a, b, c], ray, t_range)?;
+        let normal = (b - a).cross(&(c - a)).normalize();
+
+        Some(
+            Hit::new(t, ray.at(t), ray, normal, &self.material)
+                .with_uv(Point2::new(barycentric.y, barycentric.z))
+                .with_barycentric(barycentric),
+        )
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let [a, b, c] = &self.vertices;
+        Aabb::new(*a, *b).grow(c).padded(BBOX_PADDING)
+    }
+}
+
+impl<M: Material> Light for Triangle<M> {
+    /// Sample a point uniformly over the area of the triangle.
+    fn sample(&self, origin: &Point3, time: f64, u: Point2) -> Option<LightSample<'_>> {
+        let [a, b, c] = &self.vertices;
+        sample_light(self, [a, b, c], origin, time, u)
+    }
+
+    fn pdf(&self, origin: &Point3, time: f64, direction: &Vector3) -> f64 {
+        let [a, b, c] = &self.vertices;
+        light_pdf(self, [a, b, c], origin, time, direction)
+    }
+}
+
+/// Indices of the three corners of a mesh triangle in each of the mesh buffers.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MeshFace {
+    pub positions: [usize; 3],
+    pub normals: Option<[usize; 3]>,
+    pub uvs: Option<[usize; 3]>,
+}
+
+/// Triangles sharing vertex position, normal and texture coordinate buffers.
+pub struct TriangleMesh<M> {
+    positions: Vec<Point3>,
+    normals: Vec<Vector3>,
+    uvs: Vec<Point2>,
+    faces: Vec<MeshFace>,
+    material: M,
+}
+
+impl<M: Material + 'static> TriangleMesh<M> {
+    /// NOTE: the indices of every face are assumed to be within the bounds of the buffers.
+    pub fn new(
+        positions: Vec<Point3>,
+        normals: Vec<Vector3>,
+        uvs: Vec<Point2>,
+        faces: Vec<MeshFace>,
+        material: M,
+    ) -> Self {
+        Self {
+            positions,
+            normals,
+            uvs,
+            faces,
+            material,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.faces.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.faces.is_empty()
+    }
+
+    pub fn material(&self) -> &M {
+        &self.material
+    }
+
+    /// Split the mesh into one object per triangle, all sharing the mesh buffers, so that they
+    /// can be put in a [`Bvh`](crate::bvh::Bvh).
+    pub fn into_triangles(self) -> Vec<Box<dyn Hittable>> {
+        self.into_mesh_triangles()
+            .into_iter()
+            .map(|triangle| Box::new(triangle) as Box<dyn Hittable>)
+            .collect()
+    }
+
+    /// Split the mesh into its triangles, all sharing the mesh buffers.
+    pub fn into_mesh_triangles(self) -> Vec<MeshTriangle<M>> {
+        let mesh = Arc::new(self);
+        (0..mesh.faces.len())
+            .map(|face| MeshTriangle {
+                mesh: Arc::clone(&mesh),
+                face,
+            })
+            .collect()
+    }
+}
+
+/// A single triangle of a [`TriangleMesh`].
+pub struct MeshTriangle<M> {
+    mesh: Arc<TriangleMesh<M>>,
+    face: usize,
+}
+
+impl<M> Clone for MeshTriangle<M> {
+    fn clone(&self) -> Self {
+        Self {
+            mesh: Arc::clone(&self.mesh),
+            face: self.face,
+        }
+    }
+}
+
+impl<M: Material> MeshTriangle<M> {
+    pub fn material(&self) -> &M {
+        &self.mesh.material
+    }
+
+    fn vertices(&self) -> [&Point3; 3] {
+        let [a, b, c] = self.mesh.faces[self.face].positions;
+        let positions = &self.mesh.positions;
+        [&positions[a], &positions[b], &positions[c]]
+    }
+}
+
+impl<M: Material + Clone + 'static> MeshTriangle<M> {
+    /// Copy of the triangle moved by `transform` into a mesh of its own, keeping its texture
+    /// coordinates and shading normals, such as to sample an instanced triangle as a light.
+    ///
+    /// NOTE: the parameter `transform` is assumed to be invertible.
+    pub fn transformed(&self, transform: &Affine3<f64>) -> Self {
+        let face = &self.mesh.faces[self.face];
+        let linear = transform.matrix().fixed_view::<3, 3>(0, 0);
+        let normal_matrix = transform
+            .inverse()
+            .matrix()
+            .fixed_view::<3, 3>(0, 0)
+            .transpose();
+
+        // Mirroring transforms reverse the order of the corners, which is undone so that the
+        // triangle keeps facing the same side.
+        let corners = if linear.determinant() < 0.0 {
+            [0, 2, 1]
+        } else {
+            [0, 1, 2]
+        };
+
+        let positions = corners.map(|corner| {
+            let position = self.mesh.positions[face.positions[corner]];
+            (transform * nalgebra::Point3::from(position)).coords
+        });
+        let normals = face.normals.map(|normals| {
+            corners.map(|corner| (normal_matrix * self.mesh.normals[normals[corner]]).normalize())
+        });
+
+        // Texture coordinates are always given, the default ones depending on the order of the
+        // corners.
+        let uvs = corners.map(|corner| match face.uvs {
+            Some(uvs) => self.mesh.uvs[uvs[corner]],
+            None => [
+                Point2::zeros(),
+                Point2::new(1.0, 0.0),
+                Point2::new(0.0, 1.0),
+            ][corner],
+        });
+
+        let mesh = TriangleMesh::new(
+            positions.to_vec(),
+            normals.map_or_else(Vec::new, |normals| normals.to_vec()),
+            uvs.to_vec(),
+            vec![MeshFace {
+                positions: [0, 1, 2],
+                normals: normals.map(|_| [0, 1, 2]),
+                uvs: Some([0, 1, 2]),
+            }],
+            self.mesh.material.clone(),
+        );
+        Self {
+            mesh: Arc::new(mesh),
+            face: 0,
+        }
+    }
+}
+
+impl<M: Material> Hittable for MeshTriangle<M> {
+    fn hit(&self, ray: &Ray, t_range: Range<f64>) -> Option<Hit<'_>> {
+        let vertices @ [a, b, c] = self.vertices();
+        let (t, barycentric) = intersect(vertices, ray, t_range)?;
+        let face = &self.mesh.faces[self.face];
+        let normal = (b - a).cross(&(c - a)).normalize();
+
+        let uv = match face.uvs {
+            Some([a, b, c]) => {
+                let uvs = &self.mesh.uvs;
+                barycentric.x * uvs[a] + barycentric.y * uvs[b] + barycentric.z * uvs[c]
+            },
+            None => Point2::new(barycentric.y, barycentric.z),
+        };
+
+        let hit = Hit::new(t, ray.at(t), ray, normal, &self.mesh.material)
+            .with_uv(uv)
+            .with_barycentric(barycentric);
+
+        Some(match face.normals {
+            Some([a, b, c]) => {
+                let normals = &self.mesh.normals;
+                let shading_normal = barycentric.x * normals[a]
+                    + barycentric.y * normals[b]
+                    + barycentric.z * normals[c];
+                hit.with_shading_normal(shading_normal.normalize())
+            },
+            None => hit,
+        })
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let [a, b, c] = self.vertices();
+        Aabb::new(*a, *b).grow(c).padded(BBOX_PADDING)
+    }
+}
+
+impl<M: Material> Light for MeshTriangle<M> {
+    /// Sample a point uniformly over the area of the triangle.
+    fn sample(&self, origin: &Point3, time: f64, u: Point2) -> Option<LightSample<'_>> {
+        sample_light(self, self.vertices(), origin, time, u)
+    }
+
+    fn pdf(&self, origin: &Point3, time: f64, direction: &Vector3) -> f64 {
+        light_pdf(self, self.vertices(), origin, time, direction)
+    }
+}
+
+/// Sample a point uniformly over the area of the triangle `vertices` of `object`, as seen from
+/// `origin`.
+fn sample_light<'a>(
+    object: &'a dyn Hittable,
+    [a, b, c]: [&Point3; 3],
+    origin: &Point3,
+    time: f64,
+    u: Point2,
+) -> Option<LightSample<'a>> {
+    let sqrt = u.x.sqrt();
+    let point = (1.0 - sqrt) * a + sqrt * (1.0 - u.y) * b + sqrt * u.y * c;
+
+    let direction = (point - origin).normalize();
+    let hit = object.hit(
+        &Ray::new(*origin, direction).with_time(time),
+        0.0..f64::INFINITY,
+    )?;
+    Some(LightSample {
+        hit,
+        direction,
+        pdf: solid_angle_pdf([a, b, c], &direction, hit.t),
+    })
+}
+
+/// Density with respect to solid angle of [`sample_light`] returning the unit `direction`.
+fn light_pdf(
+    object: &dyn Hittable,
+    vertices: [&Point3; 3],
+    origin: &Point3,
+    time: f64,
+    direction: &Vector3,
+) -> f64 {
+    object
+        .hit(
+            &Ray::new(*origin, *direction).with_time(time),
+            0.0..f64::INFINITY,
+        )
+        .map_or(0.0, |hit| solid_angle_pdf(vertices, direction, hit.t))
+}
+
+/// Density with respect to solid angle of sampling the point at distance `t` along the unit
+/// `direction` uniformly over the area of the triangle. The geometric normal is used, shading
+/// normals not changing the area seen from the origin.
+fn solid_angle_pdf([a, b, c]: [&Point3; 3], direction: &Vector3, t: f64) -> f64 {
+    let normal = (b - a).cross(&(c - a));
+    let double_area = normal.norm();
+    if double_area <= 0.0 {
+        return 0.0;
+    }
+
+    let cosine = direction.dot(&normal).abs() / double_area;
+    if cosine <= 0.0 {
+        return 0.0;
+    }
+
+    // The ray parameter is the distance, the direction being a unit vector.
+    t.powi(2) / (cosine * 0.5 * double_area)
+}
+
+/// Möller–Trumbore ray-triangle intersection, returning the ray parameter and the barycentric
+/// coordinates of the hit point.
+fn intersect([a, b, c]: [&Point3; 3], ray: &Ray, t_range: Range<f64>) -> Option<(f64, Vector3)> {
+    const EPSILON: f64 = 1e-12;
+
+    let edge1 = b - a;
+    let edge2 = c - a;
+    let p = ray.direction.cross(&edge2);
+    let determinant = edge1.dot(&p);
+
+    // The ray is parallel to the triangle plane.
+    if determinant.abs() < EPSILON {
+        return None;
+    }
+
+    let inv_determinant = 1.0 / determinant;
+    let s = ray.origin - a;
+    let u = s.dot(&p) * inv_determinant;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+
+    let q = s.cross(&edge1);
+    let v = ray.direction.dot(&q) * inv_determinant;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let t = edge2.dot(&q) * inv_determinant;
+    if t_range.contains(&t) {
+        Some((t, Vector3::new(1.0 - u - v, u, v)))
+    } else {
+        None
+    }
+}
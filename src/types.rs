@@ -2,6 +2,14 @@ pub use self::color::*;
 
 pub mod color;
 
+use std::f64::consts::{FRAC_PI_4, PI};
+
+use rand::Rng as _;
+
+use crate::random::Rng;
+
+pub type Vector2 = nalgebra::Vector2<f64>;
+pub type Point2 = Vector2;
 pub type Vector3 = nalgebra::Vector3<f64>;
 pub type Point3 = Vector3;
 
@@ -12,10 +20,34 @@ pub struct Basis {
     pub w: Vector3,
 }
 
+impl Basis {
+    /// Orthonormal basis whose `w` axis is the unit vector `w`, following Duff et al. "Building an
+    /// Orthonormal Basis, Revisited".
+    pub fn from_w(w: Vector3) -> Self {
+        let sign = 1.0_f64.copysign(w.z);
+        let a = -1.0 / (sign + w.z);
+        let b = w.x * w.y * a;
+
+        Self {
+            u: Vector3::new(1.0 + sign * w.x * w.x * a, sign * b, -sign * w.x),
+            v: Vector3::new(b, sign + w.y * w.y * a, -w.y),
+            w,
+        }
+    }
+
+    /// World space vector of the coordinates `local` in the basis.
+    pub fn to_world(&self, local: &Vector3) -> Vector3 {
+        local.x * self.u + local.y * self.v + local.z * self.w
+    }
+}
+
 #[derive(Copy, Clone, Debug, Default, PartialEq)]
 pub struct Ray {
     pub origin: Point3,
     pub direction: Vector3,
+
+    // Instant the ray is traced at, which moving objects are seen at
+    pub time: f64,
 }
 
 impl Ray {
@@ -23,25 +55,31 @@ impl Ray {
         Self {
             origin,
             direction: direction.normalize(),
+            time: 0.0,
         }
     }
 
+    pub fn with_time(mut self, time: f64) -> Self {
+        self.time = time;
+        self
+    }
+
     pub fn at(&self, t: f64) -> Point3 {
-        (self.origin + t * self.direction).into()
+        self.origin + t * self.direction
     }
 }
 
-pub fn random_vector_in_cube() -> Vector3 {
+pub fn random_vector_in_cube(rng: &mut Rng) -> Vector3 {
     2.0 * Vector3::new(
-        rand::random::<f64>(),
-        rand::random::<f64>(),
-        rand::random::<f64>(),
+        rng.random::<f64>(),
+        rng.random::<f64>(),
+        rng.random::<f64>(),
     ) - Vector3::new(1.0, 1.0, 1.0)
 }
 
-pub fn random_vector_in_sphere() -> Vector3 {
+pub fn random_vector_in_sphere(rng: &mut Rng) -> Vector3 {
     loop {
-        let vector = random_vector_in_cube();
+        let vector = random_vector_in_cube(rng);
         let lensq = vector.norm_squared();
         if 1e-160 < lensq && lensq <= 1.0 {
             return vector;
@@ -49,12 +87,12 @@ pub fn random_vector_in_sphere() -> Vector3 {
     }
 }
 
-pub fn random_unit_vector_on_sphere() -> Vector3 {
-    random_vector_in_sphere().normalize()
+pub fn random_unit_vector_on_sphere(rng: &mut Rng) -> Vector3 {
+    random_vector_in_sphere(rng).normalize()
 }
 
-pub fn random_unit_vector_on_hemisphere(normal: &Vector3) -> Vector3 {
-    let vector = random_unit_vector_on_sphere();
+pub fn random_unit_vector_on_hemisphere(normal: &Vector3, rng: &mut Rng) -> Vector3 {
+    let vector = random_unit_vector_on_sphere(rng);
     // In the same hemisphere as the normal
     if vector.dot(normal) > 0.0 {
         vector
@@ -63,6 +101,35 @@ pub fn random_unit_vector_on_hemisphere(normal: &Vector3) -> Vector3 {
     }
 }
 
+/// Shirley and Chiu's concentric mapping of the unit square onto the unit disk, which keeps
+/// the stratification of the sample.
+pub fn concentric_disk_sample(u: Point2) -> Vector2 {
+    let offset = 2.0 * u - Vector2::new(1.0, 1.0);
+    if offset.x == 0.0 && offset.y == 0.0 {
+        return Vector2::zeros();
+    }
+
+    let (radius, theta) = if offset.x.abs() > offset.y.abs() {
+        (offset.x, FRAC_PI_4 * (offset.y / offset.x))
+    } else {
+        (
+            offset.y,
+            2.0 * FRAC_PI_4 - FRAC_PI_4 * (offset.x / offset.y),
+        )
+    };
+
+    radius * Vector2::new(theta.cos(), theta.sin())
+}
+
+/// Point of the unit sphere mapped from a point `u` of the unit square, uniformly over the area
+/// of the sphere.
+pub fn uniform_sphere_sample(u: Point2) -> Vector3 {
+    let z = 1.0 - 2.0 * u.x;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * u.y;
+    Vector3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
 /// Return true if the vector is close to zero in all dimensions.
 pub fn near_zero(vector: &Vector3) -> bool {
     const SMALLEST: f64 = 1e-8;
@@ -1,10 +1,11 @@
 use std::ops;
 
 use derive_more::{Add, AddAssign, Deref, DerefMut, From, MulAssign, Sub};
-use nalgebra::ComplexField;
 use rand::distr::uniform::SampleRange;
+use rand::Rng as _;
 
 use super::Vector3;
+use crate::random::Rng;
 
 #[derive(
     Copy, Clone, From, Deref, DerefMut, Debug, Default, PartialEq, Add, Sub, AddAssign, MulAssign,
@@ -24,26 +25,23 @@ impl Color {
         Self(Vector3::new(r, g, b))
     }
 
-    pub fn random_range(range: impl SampleRange<f64> + Clone) -> Self {
+    pub fn random_range(range: impl SampleRange<f64> + Clone, rng: &mut Rng) -> Self {
         Self::new(
-            rand::random_range(range.clone()),
-            rand::random_range(range.clone()),
-            rand::random_range(range),
+            rng.random_range(range.clone()),
+            rng.random_range(range.clone()),
+            rng.random_range(range),
         )
     }
 
-    pub fn random() -> Self {
-        Self::random_range(0.0..=1.0)
+    pub fn random(rng: &mut Rng) -> Self {
+        Self::random_range(0.0..=1.0, rng)
     }
 
-    pub fn to_byte(&self) -> [u8; 3] {
-        let (r, g, b) = (self.x, self.y, self.z);
-
-        // Translate the 0.0..=1.0 component values to the byte range 0..=255.
-        [(r * 256.0) as u8, (g * 256.0) as u8, (b * 256.0) as u8]
+    pub fn to_f32(self) -> [f32; 3] {
+        [self.x as f32, self.y as f32, self.z as f32]
     }
 
-    pub fn to_vec(&self) -> Vector3 {
+    pub fn to_vec(self) -> Vector3 {
         self.0
     }
 
@@ -55,12 +53,31 @@ impl Color {
         )
     }
 
-    pub fn to_gamma_2_color(&self) -> Self {
-        let r = self.0.x.try_sqrt().unwrap_or(0.0);
-        let g = self.0.y.try_sqrt().unwrap_or(0.0);
-        let b = self.0.z.try_sqrt().unwrap_or(0.0);
+    /// Encode linear components with the sRGB transfer function (OETF).
+    pub fn to_srgb(self) -> Self {
+        Self(self.map(|c| {
+            if c <= 0.0031308 {
+                12.92 * c
+            } else {
+                1.055 * c.powf(1.0 / 2.4) - 0.055
+            }
+        }))
+    }
+
+    /// Decode sRGB encoded components to linear ones.
+    pub fn to_linear(self) -> Self {
+        Self(self.map(|c| {
+            if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        }))
+    }
 
-        Self::new(r, g, b)
+    /// Relative luminance of linear sRGB components.
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.x + 0.7152 * self.y + 0.0722 * self.z
     }
 }
 